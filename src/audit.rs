@@ -0,0 +1,318 @@
+//! An append-only log of changes to which tools are trusted and installed.
+//!
+//! Each line of `audit.log` in the Aftman home is a JSON object describing one
+//! event. Aftman only ever appends to this file.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use fs_err::OpenOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::home::Home;
+use crate::redact::redact;
+use crate::tool_id::ToolId;
+use crate::tool_pattern::ToolPattern;
+use crate::trust::TrustRule;
+
+pub static AUDIT_LOG_FILE_NAME: &str = "audit.log";
+
+/// A single line of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the event happened, in RFC 3339 format.
+    pub time: String,
+
+    #[serde(flatten)]
+    pub event: AuditEvent,
+
+    /// The manifest that caused this event, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    Trust {
+        rule: TrustRule,
+    },
+    Untrust {
+        rule: TrustRule,
+    },
+    Download {
+        tool: ToolId,
+        url: String,
+        size: u64,
+        sha256: String,
+    },
+    Install {
+        tool: ToolId,
+    },
+    Remove {
+        tool: ToolId,
+    },
+}
+
+impl AuditEvent {
+    /// The name of this kind of event, as it appears in the log.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Trust { .. } => "trust",
+            Self::Untrust { .. } => "untrust",
+            Self::Download { .. } => "download",
+            Self::Install { .. } => "install",
+            Self::Remove { .. } => "remove",
+        }
+    }
+
+    /// Tells whether this event is about a tool matching the given pattern.
+    /// Trust events match if their pattern is the same or matches a tool name
+    /// that the given pattern would also match.
+    pub fn concerns(&self, pattern: &ToolPattern) -> bool {
+        match self {
+            Self::Trust { rule } | Self::Untrust { rule } => {
+                &rule.pattern == pattern
+                    || rule
+                        .pattern
+                        .as_ref()
+                        .parse()
+                        .map_or(false, |name| pattern.matches(&name))
+            }
+            Self::Download { tool, .. } | Self::Install { tool } | Self::Remove { tool } => {
+                pattern.matches(tool.name())
+            }
+        }
+    }
+
+    /// This event with secrets removed from its fields. URLs can include
+    /// signatures, which shouldn't be kept around.
+    fn redacted(self) -> Self {
+        match self {
+            Self::Download {
+                tool,
+                url,
+                size,
+                sha256,
+            } => Self::Download {
+                tool,
+                url: redact(&url),
+                size,
+                sha256,
+            },
+            event => event,
+        }
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Trust { rule } => write!(formatter, "trusted {rule}"),
+            Self::Untrust { rule } => write!(formatter, "untrusted {rule}"),
+            Self::Download {
+                tool,
+                url,
+                size,
+                sha256,
+            } => write!(
+                formatter,
+                "downloaded {tool} from {url} ({size} bytes, SHA-256 {sha256})"
+            ),
+            Self::Install { tool } => write!(formatter, "installed {tool}"),
+            Self::Remove { tool } => write!(formatter, "removed {tool}"),
+        }
+    }
+}
+
+pub struct AuditLog;
+
+impl AuditLog {
+    /// Append an event to the audit log, optionally noting the manifest that
+    /// caused it. The event has already happened by the time it's recorded,
+    /// so failing to record it only logs a warning.
+    pub fn record(home: &Home, event: AuditEvent, manifest: Option<&Path>) {
+        if let Err(err) = Self::append(home, event, manifest) {
+            log::warn!("Could not record event in the audit log: {err:#}");
+        }
+    }
+
+    fn append(home: &Home, event: AuditEvent, manifest: Option<&Path>) -> anyhow::Result<()> {
+        let entry = AuditEntry {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            event: event.redacted(),
+            manifest: manifest.map(Path::to_owned),
+        };
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let path = Self::path(home);
+        fs_err::create_dir_all(home.path())?;
+
+        // Each entry is written with a single append so that entries from
+        // concurrent installs never interleave.
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Could not write to audit log at {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Read every entry from the audit log, oldest first.
+    pub fn read(home: &Home) -> anyhow::Result<Vec<AuditEntry>> {
+        let path = Self::path(home);
+
+        let file = match fs_err::File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => log::warn!(
+                    "Skipping invalid entry on line {} of {}: {err}",
+                    index + 1,
+                    path.display()
+                ),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    pub fn path(home: &Home) -> PathBuf {
+        home.path().join(AUDIT_LOG_FILE_NAME)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_and_read() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        assert!(AuditLog::read(&home)?.is_empty());
+
+        let manifest = home.path().join("aftman.toml");
+        AuditLog::record(
+            &home,
+            AuditEvent::Trust {
+                rule: "foo/*".parse()?,
+            },
+            None,
+        );
+        AuditLog::record(
+            &home,
+            AuditEvent::Install {
+                tool: "foo/bar@1.0.0".parse()?,
+            },
+            Some(&manifest),
+        );
+
+        let entries = AuditLog::read(&home)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].event.kind(), "trust");
+        assert_eq!(entries[0].manifest, None);
+        assert_eq!(entries[1].event.to_string(), "installed foo/bar@1.0.0");
+        assert_eq!(entries[1].manifest.as_deref(), Some(manifest.as_path()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_urls_are_redacted() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        AuditLog::record(
+            &home,
+            AuditEvent::Download {
+                tool: "foo/bar@1.0.0".parse()?,
+                url: "https://example.com/bar.zip?X-Amz-Signature=abc".to_owned(),
+                size: 5,
+                sha256: "0123".to_owned(),
+            },
+            None,
+        );
+
+        let contents = fs_err::read_to_string(AuditLog::path(&home))?;
+        assert!(!contents.contains("X-Amz-Signature"));
+        assert!(contents.contains("https://example.com/bar.zip?[REDACTED]"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn failing_to_record_is_not_an_error() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        fs_err::create_dir_all(AuditLog::path(&home))?;
+
+        AuditLog::record(
+            &home,
+            AuditEvent::Remove {
+                tool: "foo/bar@1.0.0".parse()?,
+            },
+            None,
+        );
+        assert!(AuditLog::path(&home).is_dir());
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_invalid_lines() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        AuditLog::record(
+            &home,
+            AuditEvent::Remove {
+                tool: "foo/bar@1.0.0".parse()?,
+            },
+            None,
+        );
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(AuditLog::path(&home))?;
+        writeln!(file, "not json")?;
+
+        assert_eq!(AuditLog::read(&home)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_by_pattern() -> anyhow::Result<()> {
+        let pattern: ToolPattern = "foo/*".parse()?;
+
+        let install = AuditEvent::Install {
+            tool: "foo/bar@1.0.0".parse()?,
+        };
+        assert!(install.concerns(&pattern));
+        assert!(!install.concerns(&"other/bar".parse()?));
+
+        let trust = AuditEvent::Trust {
+            rule: "foo/bar@^1".parse()?,
+        };
+        assert!(trust.concerns(&pattern));
+        assert!(trust.concerns(&"foo/bar".parse()?));
+
+        let trust = AuditEvent::Trust {
+            rule: "foo/*".parse()?,
+        };
+        assert!(trust.concerns(&pattern));
+        assert!(!trust.concerns(&"foo/bar".parse()?));
+
+        Ok(())
+    }
+}
@@ -1,11 +1,15 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io;
 
 use anyhow::{bail, format_err, Context};
+use reqwest::header::{HeaderName, AUTHORIZATION};
 use serde::{Deserialize, Serialize};
-use toml_edit::Document;
+use toml_edit::{Document, Item, Table};
 
-use crate::config::write_if_not_exists;
+use crate::config::{write_atomic_private, write_if_not_exists};
 use crate::home::Home;
+use crate::redact::REDACTED;
 
 pub static MANIFEST_FILE_NAME: &str = "auth.toml";
 
@@ -14,18 +18,99 @@ static DEFAULT_MANIFEST: &str = r#"
 # For more information, see https://github.com/LPGhatguy/aftman
 
 # github = "token"
+
+# Tokens can also be given per host, like for a GitHub Enterprise server. The
+# type sets how the token is sent, and is one of "github" (the default),
+# "gitlab", or "bearer".
+#
+# [hosts."ghe.example.com"]
+# token = "token"
+# type = "github"
 "#;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The host that the top-level `github` token is used for.
+pub static GITHUB_HOST: &str = "github.com";
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AuthManifest {
+    /// A token for github.com. Equivalent to a `[hosts."github.com"]` entry,
+    /// which takes precedence over it.
     pub github: Option<String>,
+
+    /// Tokens keyed by the host they're used for. Each entry also applies to
+    /// subdomains of its host.
+    #[serde(default)]
+    pub hosts: BTreeMap<String, Credential>,
+}
+
+/// A token for a single host. Entries without a token get one from the
+/// credential helper instead.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credential {
+    #[serde(default)]
+    pub token: String,
+
+    #[serde(rename = "type", default)]
+    pub kind: CredentialKind,
+}
+
+/// How a token is attached to requests, which depends on the kind of server
+/// it's for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialKind {
+    /// `Authorization: token <token>`, used by GitHub and GitHub Enterprise.
+    #[default]
+    #[clap(name = "github")]
+    GitHub,
+
+    /// `PRIVATE-TOKEN: <token>`, used by GitLab.
+    #[clap(name = "gitlab")]
+    GitLab,
+
+    /// `Authorization: Bearer <token>`.
+    Bearer,
+}
+
+// Tokens are left out of debug output so that they can't end up in logs.
+impl fmt::Debug for AuthManifest {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("AuthManifest")
+            .field("github", &self.github.as_ref().map(|_| REDACTED))
+            .field("hosts", &self.hosts)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Credential")
+            .field("token", &REDACTED)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl Credential {
+    /// The header that should be added to requests using this credential.
+    pub fn header(&self) -> (HeaderName, String) {
+        match self.kind {
+            CredentialKind::GitHub => (AUTHORIZATION, format!("token {}", self.token)),
+            CredentialKind::GitLab => {
+                (HeaderName::from_static("private-token"), self.token.clone())
+            }
+            CredentialKind::Bearer => (AUTHORIZATION, format!("Bearer {}", self.token)),
+        }
+    }
 }
 
 impl AuthManifest {
     /// Create an empty global auth manifest if there isn't one already.
     pub fn init(home: &Home) -> anyhow::Result<()> {
         let base_dir = home.path();
-        fs_err::create_dir_all(&base_dir)?;
+        fs_err::create_dir_all(base_dir)?;
 
         let manifest_path = base_dir.join(MANIFEST_FILE_NAME);
         write_if_not_exists(&manifest_path, DEFAULT_MANIFEST.trim())?;
@@ -54,16 +139,332 @@ impl AuthManifest {
         Ok(Some(manifest))
     }
 
-    fn add_token(home: &Home, token_type: &str, token: &str) -> anyhow::Result<()> {
+    /// Find the credential to use for requests to the given host. When more
+    /// than one entry matches, the most specific one wins.
+    pub fn credential_for(&self, host: &str) -> Option<Credential> {
+        let from_hosts = self
+            .hosts
+            .iter()
+            .filter(|(pattern, credential)| {
+                !credential.token.is_empty() && host_matches(pattern, host)
+            })
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, credential)| credential.clone());
+
+        from_hosts.or_else(|| {
+            let token = self.github.as_ref()?;
+            host_matches(GITHUB_HOST, host).then(|| Credential {
+                token: token.clone(),
+                kind: CredentialKind::GitHub,
+            })
+        })
+    }
+
+    /// Every configured credential, keyed by host. The top-level `github`
+    /// token is listed under github.com unless that host has its own entry.
+    pub fn credentials(&self) -> BTreeMap<String, Credential> {
+        let mut credentials = self.hosts.clone();
+
+        if let Some(token) = &self.github {
+            credentials
+                .entry(GITHUB_HOST.to_owned())
+                .or_insert_with(|| Credential {
+                    token: token.clone(),
+                    kind: CredentialKind::GitHub,
+                });
+        }
+
+        credentials
+    }
+
+    /// Save a token for the given host, replacing any token it already had.
+    pub fn add_token(home: &Home, host: &str, credential: &Credential) -> anyhow::Result<()> {
+        let mut document = Self::read_document(home)?;
+
+        let hosts = document
+            .entry("hosts")
+            .or_insert_with(|| {
+                let mut hosts = Table::new();
+                hosts.set_implicit(true);
+                Item::Table(hosts)
+            })
+            .as_table_mut()
+            .with_context(|| format_err!("hosts in {MANIFEST_FILE_NAME} must be a table"))?;
+
+        let mut entry = Table::new();
+        entry["token"] = toml_edit::value(credential.token.as_str());
+        entry["type"] = toml_edit::value(credential.kind.to_string());
+        hosts.insert(host, Item::Table(entry));
+
+        // Comments at the end of the file would otherwise end up after the new
+        // entry, even though it's appended to the file.
+        let trailing = document.trailing().to_owned();
+        if !trailing.trim().is_empty() {
+            let prefix = format!("{}\n\n", trailing.trim_end());
+            if let Some(entry) = document["hosts"][host].as_table_mut() {
+                entry.decor_mut().set_prefix(prefix);
+                document.set_trailing("");
+            }
+        }
+
+        Self::write_document(home, &document)?;
+        log::info!("A token for {host} has been added globally.");
+
+        Ok(())
+    }
+
+    /// Remove the token for the given host, returning whether there was one.
+    /// Removing the token for github.com also removes the top-level `github`
+    /// token.
+    pub fn remove_token(home: &Home, host: &str) -> anyhow::Result<bool> {
+        let mut document = Self::read_document(home)?;
+
+        let mut removed = remove_entry(&mut document, &["hosts"], host);
+
+        if host == GITHUB_HOST {
+            removed |= remove_entry(&mut document, &[], "github");
+        }
+
+        if removed {
+            Self::write_document(home, &document)?;
+        }
+
+        Ok(removed)
+    }
+
+    fn read_document(home: &Home) -> anyhow::Result<Document> {
         let manifest_path = home.path().join(MANIFEST_FILE_NAME);
-        let content = fs_err::read_to_string(&manifest_path)?;
-        let mut document: Document = content.parse()?;
-        document[token_type.as_ref()] = toml_edit::value(token.to_string());
 
-        fs_err::write(&manifest_path, document.to_string())?;
+        let content = match fs_err::read_to_string(&manifest_path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
 
-        log::info!("A {token_type} token has been added globally.");
+        content
+            .parse()
+            .with_context(|| format_err!("Invalid auth.toml at {}", manifest_path.display()))
+    }
+
+    fn write_document(home: &Home, document: &Document) -> anyhow::Result<()> {
+        let manifest_path = home.path().join(MANIFEST_FILE_NAME);
+        fs_err::create_dir_all(home.path())?;
+        // This file holds tokens, so only its owner should be able to read it.
+        write_atomic_private(&manifest_path, &document.to_string())?;
 
         Ok(())
     }
 }
+
+impl fmt::Display for CredentialKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::GitHub => "github",
+            Self::GitLab => "gitlab",
+            Self::Bearer => "bearer",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// Remove an entry from a table in the document, returning whether it was
+/// there. Comments before an entry are stored with it, which includes the
+/// comments at the top of the file, so they're moved to whatever comes next
+/// instead of being removed along with it.
+fn remove_entry(document: &mut Document, path: &[&str], key: &str) -> bool {
+    let mut table = document.as_table_mut();
+    for name in path {
+        table = match table.get_mut(name).and_then(Item::as_table_mut) {
+            Some(table) => table,
+            None => return false,
+        };
+    }
+
+    let (key, item) = match table.remove_entry(key) {
+        Some(entry) => entry,
+        None => return false,
+    };
+
+    let comments = match &item {
+        Item::Table(table) => table.decor().prefix(),
+        _ => key.decor().prefix(),
+    };
+
+    match comments {
+        Some(comments) if !comments.trim().is_empty() => keep_comments(document, comments),
+        _ => {}
+    }
+
+    true
+}
+
+/// Attach comments to the first thing left in the document.
+fn keep_comments(document: &mut Document, comments: &str) {
+    let next_key = document
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_owned());
+
+    let join = |prefix: Option<&str>| {
+        let prefix = prefix.unwrap_or("").trim_start_matches(&['\r', '\n'][..]);
+        format!("{}\n\n{}", comments.trim_end(), prefix)
+    };
+
+    if let Some(decor) = next_key.and_then(|key| document.key_decor_mut(&key)) {
+        let prefix = join(decor.prefix());
+        decor.set_prefix(prefix);
+    } else if let Some(table) = first_table(document.as_table_mut()) {
+        let prefix = join(table.decor().prefix());
+        table.decor_mut().set_prefix(prefix);
+    } else {
+        let trailing = comments.to_owned() + document.trailing();
+        document.set_trailing(&trailing);
+    }
+}
+
+/// The table whose header comes first in the file.
+fn first_table(table: &mut Table) -> Option<&mut Table> {
+    table
+        .iter_mut()
+        .filter_map(|(_, item)| item.as_table_mut())
+        .filter_map(|table| {
+            if table.is_implicit() {
+                first_table(table)
+            } else {
+                Some(table)
+            }
+        })
+        .min_by_key(|table| table.position())
+}
+
+/// Tells whether `host` is `pattern` or one of its subdomains, so that a token
+/// for github.com is also used for api.github.com.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.');
+
+    host.eq_ignore_ascii_case(pattern)
+        || host
+            .len()
+            .checked_sub(pattern.len() + 1)
+            .map_or(false, |split| {
+                host.as_bytes()[split] == b'.' && host[split + 1..].eq_ignore_ascii_case(pattern)
+            })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(contents: &str) -> AuthManifest {
+        toml::from_str(contents).expect("failed to parse auth.toml")
+    }
+
+    #[test]
+    fn legacy_github_token() {
+        let auth = parse(r#"github = "abc""#);
+
+        let credential = auth.credential_for("api.github.com").unwrap();
+        assert_eq!(credential.token, "abc");
+        assert_eq!(credential.kind, CredentialKind::GitHub);
+
+        assert!(auth.credential_for("ghe.corp.net").is_none());
+        assert!(auth.credential_for("notgithub.com").is_none());
+    }
+
+    #[test]
+    fn per_host_tokens() {
+        let auth = parse(
+            r#"
+            github = "legacy"
+
+            [hosts."github.com"]
+            token = "public"
+
+            [hosts."ghe.corp.net"]
+            token = "enterprise"
+
+            [hosts."gitlab.corp.net"]
+            token = "lab"
+            type = "gitlab"
+
+            [hosts."api.gitlab.corp.net"]
+            token = "lab-api"
+            type = "bearer"
+            "#,
+        );
+
+        let token = |host: &str| auth.credential_for(host).map(|c| c.token);
+
+        assert_eq!(token("api.github.com").as_deref(), Some("public"));
+        assert_eq!(token("ghe.corp.net").as_deref(), Some("enterprise"));
+        assert_eq!(token("GHE.corp.net").as_deref(), Some("enterprise"));
+        assert_eq!(token("gitlab.corp.net").as_deref(), Some("lab"));
+        assert_eq!(token("api.gitlab.corp.net").as_deref(), Some("lab-api"));
+        assert_eq!(token("corp.net"), None);
+    }
+
+    #[test]
+    fn add_and_remove_tokens() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        AuthManifest::init(&home)?;
+        let path = home.path().join(MANIFEST_FILE_NAME);
+
+        let mut contents = fs_err::read_to_string(&path)?;
+        contents.push_str("\ngithub = \"legacy\"\n");
+        fs_err::write(&path, contents)?;
+
+        let credential = Credential {
+            token: "lab".to_owned(),
+            kind: CredentialKind::GitLab,
+        };
+        AuthManifest::add_token(&home, "gitlab.corp.net", &credential)?;
+
+        let auth = AuthManifest::load(&home)?.unwrap();
+        assert_eq!(auth.hosts["gitlab.corp.net"], credential);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs_err::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(auth.credentials()["github.com"].token, "legacy");
+
+        assert!(AuthManifest::remove_token(&home, "github.com")?);
+        assert!(!AuthManifest::remove_token(&home, "github.com")?);
+        assert!(AuthManifest::remove_token(&home, "gitlab.corp.net")?);
+
+        let auth = AuthManifest::load(&home)?.unwrap();
+        assert!(auth.credentials().is_empty());
+
+        // Comments in the file are left alone, even though they were attached
+        // to the top-level token.
+        assert!(fs_err::read_to_string(&path)?.starts_with("# This file is for auth tokens"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn headers() {
+        let credential = |kind| Credential {
+            token: "abc".to_owned(),
+            kind,
+        };
+
+        let (name, value) = credential(CredentialKind::GitHub).header();
+        assert_eq!(
+            (name.as_str(), value.as_str()),
+            ("authorization", "token abc")
+        );
+
+        let (name, value) = credential(CredentialKind::GitLab).header();
+        assert_eq!((name.as_str(), value.as_str()), ("private-token", "abc"));
+
+        let (name, value) = credential(CredentialKind::Bearer).header();
+        assert_eq!(
+            (name.as_str(), value.as_str()),
+            ("authorization", "Bearer abc")
+        );
+    }
+}
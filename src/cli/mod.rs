@@ -1,33 +1,73 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env::current_dir;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use anyhow::{bail, Context};
 use clap::Parser;
+use semver::Version;
+use serde::Serialize;
 
+use crate::audit::{AuditEntry, AuditEvent, AuditLog};
+use crate::auth::{AuthManifest, Credential, CredentialKind, GITHUB_HOST};
+use crate::credentials::{CredentialSource, CredentialStore};
+use crate::doctor::{self, Status};
 use crate::home::Home;
 use crate::manifest::Manifest;
+use crate::output::{self, ErrorOutput, OutputFormat};
+use crate::redact;
+use crate::resolve::{resolve, Overrides, Resolution};
+use crate::settings::Settings;
 use crate::tool_alias::ToolAlias;
+use crate::tool_id::ToolId;
 use crate::tool_name::ToolName;
+use crate::tool_pattern::ToolPattern;
+use crate::tool_source::{GitHubSource, TokenInfo};
 use crate::tool_spec::ToolSpec;
-use crate::tool_storage::{InstalledToolsCache, ToolStorage};
-use crate::trust::{TrustCache, TrustMode};
+use crate::tool_storage::{InstallStatus, InstalledToolsCache, ToolStorage, VerifyStatus};
+use crate::trust::{TrustCache, TrustEntry, TrustMode, TrustPolicy, TrustRule, TrustStatus};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
 pub struct Args {
+    /// The format to print output in. The JSON format is intended for scripts
+    /// and other tools.
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Shorthand for `--format json`.
+    #[clap(long, global = true)]
+    pub json: bool,
+
     #[clap(subcommand)]
     pub subcommand: Subcommand,
 }
 
 impl Args {
+    /// The output format that was asked for.
+    pub fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
+
     pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
         match self.subcommand {
             Subcommand::Init(sub) => sub.run(),
             Subcommand::List(sub) => sub.run(home),
             Subcommand::Add(sub) => sub.run(tools),
             Subcommand::Install(sub) => sub.run(tools),
-            Subcommand::Trust(sub) => sub.run(home),
+            Subcommand::Trust(sub) => sub.run(home, tools),
+            Subcommand::Untrust(sub) => sub.run(home, tools),
+            Subcommand::Which(sub) => sub.run(home, tools),
+            Subcommand::Run(sub) => sub.run(home, tools),
+            Subcommand::Verify(sub) => sub.run(home, tools),
+            Subcommand::Doctor(sub) => sub.run(home, tools),
+            Subcommand::Log(sub) => sub.run(home),
+            Subcommand::Auth(sub) => sub.run(home),
             Subcommand::SelfInstall(sub) => sub.run(home, tools),
 
             Subcommand::Update(_) => bail!("This command is not yet implemented."),
@@ -44,6 +84,13 @@ pub enum Subcommand {
     Update(UpdateSubcommand),
     Install(InstallSubcommand),
     Trust(TrustSubcommand),
+    Untrust(UntrustSubcommand),
+    Which(WhichSubcommand),
+    Run(RunSubcommand),
+    Verify(VerifySubcommand),
+    Doctor(DoctorSubcommand),
+    Log(LogSubcommand),
+    Auth(AuthSubcommand),
     SelfUpdate(SelfUpdateSubcommand),
     SelfInstall(SelfInstallSubcommand),
 }
@@ -87,11 +134,32 @@ impl ListSubcommand {
                 .push(tool.version().clone());
         }
 
-        for (tool, mut versions) in tools {
-            println!("{tool}");
-
+        for versions in tools.values_mut() {
             versions.sort();
             versions.reverse();
+        }
+
+        if output::format() == OutputFormat::Json {
+            let current_dir = current_dir().context("Could not read current directory")?;
+            let manifests = Manifest::discover(home, &current_dir)?;
+
+            return output::print_json(&ListOutput {
+                tools: tools
+                    .into_iter()
+                    .map(|(name, versions)| ListToolOutput { name, versions })
+                    .collect(),
+                manifests: manifests
+                    .iter()
+                    .map(|manifest| ManifestOutput {
+                        path: manifest.path.as_deref(),
+                        tools: &manifest.tools,
+                    })
+                    .collect(),
+            });
+        }
+
+        for (tool, versions) in tools {
+            println!("{tool}");
 
             let versions = versions
                 .iter()
@@ -106,6 +174,24 @@ impl ListSubcommand {
     }
 }
 
+#[derive(Debug, Serialize)]
+struct ListOutput<'a> {
+    tools: Vec<ListToolOutput>,
+    manifests: Vec<ManifestOutput<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListToolOutput {
+    name: String,
+    versions: Vec<Version>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestOutput<'a> {
+    path: Option<&'a Path>,
+    tools: &'a BTreeMap<ToolAlias, ToolId>,
+}
+
 /// Adds a new tool to Aftman and installs it.
 #[derive(Debug, Parser)]
 pub struct AddSubcommand {
@@ -124,10 +210,25 @@ pub struct AddSubcommand {
 
 impl AddSubcommand {
     pub fn run(self, tools: ToolStorage) -> anyhow::Result<()> {
-        tools.add(&self.tool_spec, self.tool_alias.as_ref(), self.global)
+        let id = tools.add(&self.tool_spec, self.tool_alias.as_ref(), self.global)?;
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&AddOutput {
+                id: &id,
+                global: self.global,
+            })?;
+        }
+
+        Ok(())
     }
 }
 
+#[derive(Debug, Serialize)]
+struct AddOutput<'a> {
+    id: &'a ToolId,
+    global: bool,
+}
+
 /// Updates one or more tools that are managed by Aftman.
 ///
 /// Tools can be specified either by their alias or by their name.
@@ -158,39 +259,549 @@ pub struct InstallSubcommand {
     /// Skip / don't error if a tool was not trusted during install.
     #[clap(long)]
     pub skip_untrusted: bool,
+    /// Only install tools allowed by the given trust policy file, without
+    /// prompting. Any other tool is treated as untrusted, even if it was
+    /// trusted before. Intended for CI machines.
+    #[clap(long, conflicts_with = "no-trust-check")]
+    pub trust_policy: Option<PathBuf>,
+    /// The maximum number of tools to download and install at the same time.
+    #[clap(long, short, default_value_t = 4)]
+    pub jobs: usize,
 }
 
 impl InstallSubcommand {
     pub fn run(self, tools: ToolStorage) -> anyhow::Result<()> {
-        let trust = if self.no_trust_check {
+        let trust = if let Some(path) = &self.trust_policy {
+            TrustMode::Policy(TrustPolicy::load(path)?)
+        } else if self.no_trust_check {
             TrustMode::NoCheck
         } else {
             TrustMode::Check
         };
 
-        tools.install_all(trust, self.skip_untrusted)
+        let summary = tools.install_all(trust, self.skip_untrusted, self.jobs)?;
+
+        if output::format() == OutputFormat::Json {
+            let errors: BTreeMap<_, _> = summary
+                .failures
+                .iter()
+                .chain(&summary.trust_errors)
+                .map(|(id, err)| (id, ErrorOutput::new(err)))
+                .collect();
+
+            output::print_json(&InstallOutput {
+                tools: summary
+                    .tools
+                    .iter()
+                    .map(|(id, status)| InstallToolOutput {
+                        id,
+                        status: *status,
+                        error: errors.get(id),
+                    })
+                    .collect(),
+            })?;
+        } else {
+            summary.print();
+        }
+
+        summary.into_result()
     }
 }
 
-/// Mark the given tool name as being trusted.
+#[derive(Debug, Serialize)]
+struct InstallOutput<'a> {
+    tools: Vec<InstallToolOutput<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct InstallToolOutput<'a> {
+    id: &'a ToolId,
+    status: InstallStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a ErrorOutput>,
+}
+
+/// Mark the given tool name as being trusted, or list trusted tools.
 #[derive(Debug, Parser)]
 pub struct TrustSubcommand {
-    /// The tool to mark as trusted.
-    pub name: ToolName,
+    /// The tool to mark as trusted, like SCOPE/NAME. Use a pattern like
+    /// SCOPE/* to trust every tool from a user or organization.
+    ///
+    /// A version range can be given to only trust some versions of a tool,
+    /// like SCOPE/NAME@^7. Ranges are only enforced when strict trust is
+    /// enabled in settings.
+    #[clap(required_unless_present = "list")]
+    pub name: Option<TrustRule>,
+
+    /// List every trusted tool and pattern instead, including those from
+    /// trust policies.
+    #[clap(long, conflicts_with = "name")]
+    pub list: bool,
 }
 
 impl TrustSubcommand {
-    pub fn run(self, home: &Home) -> anyhow::Result<()> {
-        if TrustCache::add(home, self.name.clone())? {
-            log::info!("Added {} to the set of trusted tools.", self.name);
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let name = match self.name {
+            Some(name) => name,
+            None => return Self::list(tools),
+        };
+
+        let added = TrustCache::add(home, name.clone())?;
+        if added {
+            AuditLog::record(home, AuditEvent::Trust { rule: name.clone() }, None);
+        }
+
+        if added && name.pattern.is_wildcard() {
+            log::info!(
+                "Added {} to the set of trusted tools. \
+                 Every tool matching this pattern will be installed without asking.",
+                name
+            );
+        } else if added {
+            log::info!("Added {} to the set of trusted tools.", name);
+        } else {
+            log::info!("{} was already a trusted tool.", name);
+        }
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&TrustOutput { name: &name, added })?;
+        }
+
+        Ok(())
+    }
+
+    fn list(tools: ToolStorage) -> anyhow::Result<()> {
+        let entries = tools.trust_entries()?;
+
+        if output::format() == OutputFormat::Json {
+            return output::print_json(&TrustListOutput { trusted: &entries });
+        }
+
+        for entry in &entries {
+            let rule = TrustRule::new(entry.pattern.clone(), entry.versions.clone());
+
+            match &entry.policy {
+                Some(policy) => println!("{} (from {})", rule, policy.display()),
+                None => println!("{}", rule),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TrustOutput<'a> {
+    name: &'a TrustRule,
+    added: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TrustListOutput<'a> {
+    trusted: &'a [TrustEntry],
+}
+
+/// Remove a tool or pattern from the set of trusted tools.
+#[derive(Debug, Parser)]
+pub struct UntrustSubcommand {
+    /// The tool or pattern to stop trusting. This must match an entry listed
+    /// by `aftman trust --list` exactly, including any version range.
+    pub name: TrustRule,
+
+    /// Also delete every installed version of the matching tools, and the
+    /// aliases that manifests reachable from this directory use for them.
+    #[clap(long)]
+    pub purge: bool,
+}
+
+impl UntrustSubcommand {
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let removed = TrustCache::remove(home, &self.name)?;
+        if removed {
+            let rule = self.name.clone();
+            AuditLog::record(home, AuditEvent::Untrust { rule }, None);
+        }
+
+        if removed {
+            log::info!("Removed {} from the set of trusted tools.", self.name);
+        } else {
+            log::info!("{} was not in the set of trusted tools.", self.name);
+        }
+
+        // An exact name might still be trusted through a pattern or a policy.
+        if let Ok(name) = self.name.pattern.as_ref().parse::<ToolName>() {
+            if tools.trust_status(&name, None)? == TrustStatus::Trusted {
+                log::warn!(
+                    "{name} is still trusted by another entry. \
+                     Run `aftman trust --list` to see every trusted tool."
+                );
+            }
+        }
+
+        let mut uninstalled = Vec::new();
+        let mut unlinked = Vec::new();
+
+        if self.purge {
+            uninstalled = tools.uninstall(&self.name)?;
+
+            let current_dir = current_dir().context("Could not read current directory")?;
+            let manifests = Manifest::discover(home, &current_dir)?;
+            let aliases: BTreeSet<&ToolAlias> = manifests
+                .iter()
+                .flat_map(|manifest| &manifest.tools)
+                .filter(|(_, id)| self.name.allows(id.name(), Some(id.version())))
+                .map(|(alias, _)| alias)
+                .collect();
+
+            for alias in aliases {
+                if tools.unlink(alias)? {
+                    log::info!("Removed alias {alias}");
+                    unlinked.push(alias.clone());
+                }
+            }
+        }
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&UntrustOutput {
+                name: &self.name,
+                removed,
+                uninstalled,
+                unlinked,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct UntrustOutput<'a> {
+    name: &'a TrustRule,
+    removed: bool,
+    uninstalled: Vec<ToolId>,
+    unlinked: Vec<ToolAlias>,
+}
+
+/// Explain which tool an alias runs from the current directory, and why.
+#[derive(Debug, Parser)]
+pub struct WhichSubcommand {
+    /// The alias to look up.
+    pub alias: ToolAlias,
+}
+
+impl WhichSubcommand {
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let current_dir = current_dir().context("Could not read current directory")?;
+        let manifests = Manifest::discover(home, &current_dir)?;
+
+        let resolution = match resolve(&manifests, &Overrides::from_env(), self.alias.as_ref())? {
+            Some(resolution) => resolution,
+            None => bail!(
+                "No aftman.toml files reachable from this directory list {}.",
+                self.alias
+            ),
+        };
+
+        let id = &resolution.winner.id;
+        let exe_path = tools.exe_path(id);
+        let installed = tools.is_tool_installed(id)?;
+        let trusted =
+            tools.trust_status(id.name(), tools.trust_version(id))? == TrustStatus::Trusted;
+
+        if output::format() == OutputFormat::Json {
+            return output::print_json(&WhichOutput {
+                alias: &self.alias,
+                resolution: &resolution,
+                exe_path: &exe_path,
+                installed,
+                trusted,
+            });
+        }
+
+        println!("{} runs {id}", self.alias);
+        println!("  defined by {}", resolution.winner.source);
+
+        for shadowed in &resolution.shadowed {
+            println!("  shadows {} from {}", shadowed.id, shadowed.source);
+        }
+
+        println!("  executable: {}", exe_path.display());
+        println!("  installed: {}", if installed { "yes" } else { "no" });
+        println!("  trusted: {}", if trusted { "yes" } else { "no" });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WhichOutput<'a> {
+    alias: &'a ToolAlias,
+    #[serde(flatten)]
+    resolution: &'a Resolution,
+    exe_path: &'a Path,
+    installed: bool,
+    trusted: bool,
+}
+
+/// Run a specific version of a tool without adding it to any manifest.
+///
+/// The tool is installed first if needed.
+#[derive(Debug, Parser)]
+pub struct RunSubcommand {
+    /// The tool to run. Either a tool spec like SCOPE/NAME or SCOPE/NAME@VERSION,
+    /// or an alias from a nearby aftman.toml file like ALIAS or ALIAS@VERSION.
+    pub target: ToolTarget,
+
+    /// Arguments to pass to the tool.
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl RunSubcommand {
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let spec = self.target.into_spec(home)?;
+
+        let id = match spec.version() {
+            Some(version) => ToolId::new(spec.name().clone(), version.clone()),
+            None => tools.install_inexact(&spec, &TrustMode::Check, None)?,
+        };
+
+        let code = tools.run(&id, self.args)?;
+        std::process::exit(code);
+    }
+}
+
+/// A tool given on the command line, either as a tool spec or as an alias from
+/// a nearby manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolTarget {
+    Spec(ToolSpec),
+    Alias(ToolAlias, Option<Version>),
+}
+
+impl ToolTarget {
+    /// Turn this target into a tool spec, resolving aliases from the current
+    /// directory.
+    fn into_spec(self, home: &Home) -> anyhow::Result<ToolSpec> {
+        match self {
+            Self::Spec(spec) => Ok(spec),
+            Self::Alias(alias, version) => {
+                let current_dir = current_dir().context("Could not read current directory")?;
+                let manifests = Manifest::discover(home, &current_dir)?;
+
+                let overrides = Overrides::from_env();
+                let resolution = match resolve(&manifests, &overrides, alias.as_ref())? {
+                    Some(resolution) => resolution,
+                    None => bail!(
+                        "No aftman.toml files reachable from this directory list {alias}. \
+                         Use a tool spec like SCOPE/NAME@VERSION instead."
+                    ),
+                };
+
+                let id = resolution.winner.id;
+                let version = version.unwrap_or_else(|| id.version().clone());
+                Ok(ToolSpec::new(id.name().clone(), Some(version)))
+            }
+        }
+    }
+}
+
+impl FromStr for ToolTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        if value.contains('/') {
+            return Ok(Self::Spec(value.parse()?));
+        }
+
+        let mut alias_version = value.splitn(2, '@');
+        let alias = alias_version.next().unwrap().parse()?;
+        let version = alias_version
+            .next()
+            .map(|version| version.parse().context("Invalid version"))
+            .transpose()?;
+
+        Ok(Self::Alias(alias, version))
+    }
+}
+
+/// Check installed tools against the hashes recorded when they were installed.
+#[derive(Debug, Parser)]
+pub struct VerifySubcommand {
+    /// The tool to check. Either a tool spec like SCOPE/NAME or SCOPE/NAME@VERSION,
+    /// or an alias from a nearby aftman.toml file like ALIAS or ALIAS@VERSION.
+    /// If no tool is given, every installed tool is checked.
+    pub target: Option<ToolTarget>,
+
+    /// Download and install tools that fail verification again.
+    #[clap(long)]
+    pub repair: bool,
+
+    /// Record the current hash of tools that have no recorded hash, like
+    /// tools installed before Aftman recorded hashes. Only use this for tools
+    /// you know haven't been modified.
+    #[clap(long)]
+    pub record: bool,
+}
+
+impl VerifySubcommand {
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let installed = tools.installed_tools()?;
+
+        let ids: Vec<ToolId> = match self.target {
+            None => installed.into_iter().collect(),
+            Some(target) => {
+                let spec = target.into_spec(home)?;
+                let ids: Vec<ToolId> = installed
+                    .into_iter()
+                    .filter(|id| id.name() == spec.name())
+                    .filter(|id| {
+                        spec.version()
+                            .map_or(true, |version| id.version() == version)
+                    })
+                    .collect();
+
+                if ids.is_empty() {
+                    bail!("{spec} is not installed.");
+                }
+                ids
+            }
+        };
+
+        if ids.is_empty() {
+            log::info!("No installed tools to verify.");
+        }
+
+        let mut results = Vec::new();
+        for id in ids {
+            let status = tools.verify(&id)?;
+            let mut result = VerifyToolOutput {
+                id,
+                status,
+                repaired: false,
+                recorded: false,
+                error: None,
+            };
+
+            if self.repair && status != VerifyStatus::Ok {
+                log::info!("Repairing {} ({status})", result.id);
+                match tools.repair(&result.id) {
+                    Ok(()) => result.repaired = true,
+                    Err(err) => result.error = Some(format!("{err:#}")),
+                }
+            } else if self.record && status == VerifyStatus::Unrecorded {
+                match tools.record_hash(&result.id) {
+                    Ok(()) => result.recorded = true,
+                    Err(err) => result.error = Some(format!("{err:#}")),
+                }
+            }
+
+            results.push(result);
+        }
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&VerifyOutput { tools: &results })?;
         } else {
-            log::info!("{} was already a trusted tool.", self.name);
+            for result in &results {
+                match &result.error {
+                    Some(error) => println!("{}: {} ({error})", result.id, result.status),
+                    None if result.repaired => {
+                        println!("{}: {} (repaired)", result.id, result.status)
+                    }
+                    None if result.recorded => {
+                        println!("{}: {} (recorded now)", result.id, result.status)
+                    }
+                    None => println!("{}: {}", result.id, result.status),
+                }
+            }
+        }
+
+        let failed = results
+            .iter()
+            .filter(|result| {
+                result.error.is_some() || (result.status.is_failure() && !result.repaired)
+            })
+            .count();
+        if failed > 0 {
+            bail!(
+                "{failed} tool(s) failed verification. Run `aftman verify --repair` to reinstall them."
+            );
         }
 
         Ok(())
     }
 }
 
+#[derive(Debug, Serialize)]
+struct VerifyOutput<'a> {
+    tools: &'a [VerifyToolOutput],
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyToolOutput {
+    id: ToolId,
+    status: VerifyStatus,
+    repaired: bool,
+    recorded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Check the Aftman installation for common problems.
+#[derive(Debug, Parser)]
+pub struct DoctorSubcommand {
+    /// Fix any problems that can be fixed safely.
+    #[clap(long)]
+    pub fix: bool,
+}
+
+impl DoctorSubcommand {
+    pub fn run(self, home: &Home, tools: ToolStorage) -> anyhow::Result<()> {
+        let current_dir = current_dir().context("Could not read current directory")?;
+        let mut checks = doctor::run_checks(home, &tools, &current_dir)?;
+
+        if self.fix {
+            doctor::fix_all(&mut checks, home, &tools)?;
+        }
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&DoctorOutput { checks: &checks })?;
+        } else {
+            for check in &checks {
+                println!("[{}] {}", check.status, check.message);
+
+                if check.fixed {
+                    println!("  fixed");
+                } else if let Some(suggestion) = &check.suggestion {
+                    println!("  {suggestion}");
+                }
+            }
+        }
+
+        let remaining = checks
+            .iter()
+            .filter(|check| check.status != Status::Ok && !check.fixed);
+
+        if !self.fix && remaining.clone().any(|check| check.is_fixable()) {
+            log::info!("Run `aftman doctor --fix` to fix some of these problems automatically.");
+        }
+
+        let errors = remaining
+            .filter(|check| check.status == Status::Error)
+            .count();
+        if errors > 0 {
+            bail!("Found {errors} problem(s) with this Aftman installation.");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DoctorOutput<'a> {
+    checks: &'a [doctor::Check],
+}
+
 /// Update Aftman from the internet.
 #[derive(Debug, Parser)]
 pub struct SelfUpdateSubcommand {}
@@ -215,3 +826,332 @@ impl SelfInstallSubcommand {
         Ok(())
     }
 }
+
+/// Show the audit log of trust decisions, downloads, installs, and removals.
+#[derive(Debug, Parser)]
+pub struct LogSubcommand {
+    /// Only show events about tools matching this name or pattern, like
+    /// SCOPE/NAME or SCOPE/*.
+    pub tool: Option<ToolPattern>,
+
+    /// Only show events of this kind.
+    #[clap(long, value_parser = ["trust", "untrust", "download", "install", "remove"])]
+    pub event: Option<String>,
+
+    /// Only show this many of the most recent events.
+    #[clap(long)]
+    pub limit: Option<usize>,
+}
+
+impl LogSubcommand {
+    pub fn run(self, home: &Home) -> anyhow::Result<()> {
+        let mut entries: Vec<AuditEntry> = AuditLog::read(home)?
+            .into_iter()
+            .filter(|entry| match &self.tool {
+                Some(pattern) => entry.event.concerns(pattern),
+                None => true,
+            })
+            .filter(|entry| match &self.event {
+                Some(kind) => entry.event.kind() == kind,
+                None => true,
+            })
+            .collect();
+
+        if let Some(limit) = self.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+
+        if output::format() == OutputFormat::Json {
+            return output::print_json(&LogOutput { entries: &entries });
+        }
+
+        for entry in &entries {
+            match &entry.manifest {
+                Some(manifest) => println!(
+                    "{}  {} (from {})",
+                    entry.time,
+                    entry.event,
+                    manifest.display()
+                ),
+                None => println!("{}  {}", entry.time, entry.event),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LogOutput<'a> {
+    entries: &'a [AuditEntry],
+}
+
+/// Manage the tokens Aftman uses to authenticate with GitHub and other hosts.
+#[derive(Debug, Parser)]
+pub struct AuthSubcommand {
+    #[clap(subcommand)]
+    pub command: AuthCommand,
+}
+
+#[derive(Debug, Parser)]
+pub enum AuthCommand {
+    Login(AuthLoginSubcommand),
+    Logout(AuthLogoutSubcommand),
+    Status(AuthStatusSubcommand),
+}
+
+impl AuthSubcommand {
+    pub fn run(self, home: &Home) -> anyhow::Result<()> {
+        match self.command {
+            AuthCommand::Login(sub) => sub.run(home),
+            AuthCommand::Logout(sub) => sub.run(home),
+            AuthCommand::Status(sub) => sub.run(home),
+        }
+    }
+}
+
+/// Turns a host given on the command line into the host name used in
+/// auth.toml. `github` is short for github.com.
+fn auth_host(host: &str) -> &str {
+    match host {
+        "github" => "github.com",
+        host => host,
+    }
+}
+
+/// Save a token for a host. The token is read from stdin, or prompted for if
+/// stdin is a terminal.
+#[derive(Debug, Parser)]
+pub struct AuthLoginSubcommand {
+    /// The host to save a token for, like github.com or ghe.example.com.
+    /// `github` is short for github.com.
+    #[clap(default_value = "github")]
+    pub host: String,
+
+    /// How the token is sent to the host.
+    #[clap(long = "type", value_enum, default_value = "github")]
+    pub kind: CredentialKind,
+}
+
+impl AuthLoginSubcommand {
+    pub fn run(self, home: &Home) -> anyhow::Result<()> {
+        let host = auth_host(&self.host);
+
+        let token = if atty::is(atty::Stream::Stdin) {
+            dialoguer::Password::new()
+                .with_prompt(format!("Paste a token for {host}"))
+                .interact()?
+        } else {
+            let mut token = String::new();
+            std::io::stdin()
+                .read_to_string(&mut token)
+                .context("Could not read token from stdin")?;
+            token
+        };
+
+        let token = token.trim();
+        if token.is_empty() {
+            bail!("No token was given.");
+        }
+        redact::add_secret(token);
+
+        let credential = Credential {
+            token: token.to_owned(),
+            kind: self.kind,
+        };
+        AuthManifest::add_token(home, host, &credential)?;
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&AuthLoginOutput {
+                host,
+                kind: self.kind,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuthLoginOutput<'a> {
+    host: &'a str,
+    #[serde(rename = "type")]
+    kind: CredentialKind,
+}
+
+/// Remove the token for a host.
+#[derive(Debug, Parser)]
+pub struct AuthLogoutSubcommand {
+    /// The host to remove the token for. `github` is short for github.com.
+    #[clap(default_value = "github")]
+    pub host: String,
+}
+
+impl AuthLogoutSubcommand {
+    pub fn run(self, home: &Home) -> anyhow::Result<()> {
+        let host = auth_host(&self.host);
+        let removed = AuthManifest::remove_token(home, host)?;
+
+        if removed {
+            log::info!("Removed the token for {host}.");
+        } else {
+            log::info!("There was no token for {host}.");
+        }
+
+        if output::format() == OutputFormat::Json {
+            output::print_json(&AuthLogoutOutput { host, removed })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuthLogoutOutput<'a> {
+    host: &'a str,
+    removed: bool,
+}
+
+/// Check each saved token against its host, showing the user it belongs to,
+/// its scopes, and how many API requests it has left.
+#[derive(Debug, Parser)]
+pub struct AuthStatusSubcommand {
+    /// Only check the token for this host. `github` is short for github.com.
+    pub host: Option<String>,
+}
+
+impl AuthStatusSubcommand {
+    pub fn run(self, home: &Home) -> anyhow::Result<()> {
+        let settings = Settings::load(home)?;
+        let credentials =
+            CredentialStore::new(AuthManifest::load(home)?, settings.credential_helper);
+
+        let hosts = match &self.host {
+            Some(host) => vec![auth_host(host).to_owned()],
+            None => {
+                let mut hosts = credentials.known_hosts();
+                if credentials.has_helper() && !hosts.iter().any(|host| host == GITHUB_HOST) {
+                    hosts.push(GITHUB_HOST.to_owned());
+                }
+                hosts
+            }
+        };
+
+        let github = GitHubSource::new(credentials);
+        let mut statuses = Vec::new();
+
+        for host in hosts {
+            let (credential, source) = match github.credentials().find(&host) {
+                Some(found) => found,
+                None if self.host.is_some() => {
+                    bail!(
+                        "There is no token for {host}. Run `aftman auth login {host}` to add one."
+                    )
+                }
+                None => continue,
+            };
+
+            let (info, error) = match credential.kind {
+                CredentialKind::GitHub => match github.token_info(&host, &credential) {
+                    Ok(info) => (Some(info), None),
+                    Err(err) => (None, Some(err.to_string())),
+                },
+                _ => (None, None),
+            };
+
+            statuses.push(AuthStatusOutput {
+                host,
+                kind: credential.kind,
+                source,
+                info,
+                error,
+            });
+        }
+
+        if output::format() == OutputFormat::Json {
+            return output::print_json(&AuthStatusListOutput { hosts: &statuses });
+        }
+
+        if statuses.is_empty() {
+            println!("No tokens are configured. Run `aftman auth login` to add one.");
+        }
+
+        for status in &statuses {
+            match (&status.info, &status.error) {
+                (Some(info), _) => println!("{}: logged in as {}", status.host, info.user),
+                (None, Some(error)) => println!("{}: {}", status.host, error),
+                (None, None) => println!(
+                    "{}: a {} token is configured, but can't be checked",
+                    status.host, status.kind
+                ),
+            }
+
+            println!("  Token from: {}", status.source);
+
+            if let Some(info) = &status.info {
+                if info.scopes.is_empty() {
+                    println!("  Scopes: none");
+                } else {
+                    println!("  Scopes: {}", info.scopes.join(", "));
+                }
+
+                if let Some(rate_limit) = &info.rate_limit {
+                    println!(
+                        "  Requests remaining: {}/{}",
+                        rate_limit.remaining, rate_limit.limit
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuthStatusListOutput<'a> {
+    hosts: &'a [AuthStatusOutput],
+}
+
+#[derive(Debug, Serialize)]
+struct AuthStatusOutput {
+    host: String,
+    #[serde(rename = "type")]
+    kind: CredentialKind,
+    #[serde(flatten)]
+    source: CredentialSource,
+    #[serde(flatten)]
+    info: Option<TokenInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_tool_target() {
+        fn test(input: &str, expected: ToolTarget) {
+            let parsed: ToolTarget = input.parse().expect("failed to parse ToolTarget");
+            assert_eq!(parsed, expected);
+        }
+
+        test(
+            "rojo-rbx/rojo",
+            ToolTarget::Spec("rojo-rbx/rojo".parse().unwrap()),
+        );
+        test(
+            "rojo-rbx/rojo@7.4.0",
+            ToolTarget::Spec("rojo-rbx/rojo@7.4.0".parse().unwrap()),
+        );
+        test("rojo", ToolTarget::Alias("rojo".parse().unwrap(), None));
+        test(
+            "rojo@7.4.0",
+            ToolTarget::Alias("rojo".parse().unwrap(), Some(Version::new(7, 4, 0))),
+        );
+
+        assert!("rojo@".parse::<ToolTarget>().is_err());
+        assert!("rojo@seven".parse::<ToolTarget>().is_err());
+    }
+}
@@ -1,10 +1,12 @@
 #![allow(unused)]
 
+use std::fs::Permissions;
 use std::io::{self, BufWriter, Write};
 use std::path::Path;
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use fs_err::OpenOptions;
+use tempfile::NamedTempFile;
 
 pub fn write_only_new(path: &Path, contents: &str) -> anyhow::Result<()> {
     let mut file = match OpenOptions::new().create_new(true).write(true).open(path) {
@@ -41,3 +43,91 @@ pub fn write_if_not_exists(path: &Path, contents: &str) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Replace the contents of the file at `path` without ever leaving a partially
+/// written file behind.
+///
+/// The new contents are written to a temporary file in the same directory,
+/// flushed to disk, and then renamed over the top of the old file. The file
+/// keeps the permissions it had before, and new files are readable by
+/// everyone, since other users may share the same Aftman home.
+pub fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let permissions = match fs_err::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => default_permissions(0o644),
+        Err(err) => bail!(err),
+    };
+
+    persist_atomic(path, contents, permissions)
+}
+
+/// Like [`write_atomic`], but the file is only ever readable by its owner.
+/// Used for files that hold secrets, like auth.toml.
+pub fn write_atomic_private(path: &Path, contents: &str) -> anyhow::Result<()> {
+    persist_atomic(path, contents, default_permissions(0o600))
+}
+
+#[cfg(unix)]
+fn default_permissions(mode: u32) -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn default_permissions(_mode: u32) -> Option<Permissions> {
+    None
+}
+
+fn persist_atomic(
+    path: &Path,
+    contents: &str,
+    permissions: Option<Permissions>,
+) -> anyhow::Result<()> {
+    let parent = path
+        .parent()
+        .with_context(|| format!("{} has no parent directory", path.display()))?;
+
+    // Temporary files are created readable only by their owner, and renaming
+    // one over the old file would keep that.
+    let mut file = NamedTempFile::new_in(parent)?;
+    if let Some(permissions) = permissions {
+        fs_err::set_permissions(file.path(), permissions)?;
+    }
+
+    file.write_all(contents.as_bytes())?;
+    file.as_file().sync_all()?;
+    file.persist(path)
+        .with_context(|| format!("Could not write {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn mode(path: &Path) -> anyhow::Result<u32> {
+        Ok(fs_err::metadata(path)?.permissions().mode() & 0o777)
+    }
+
+    #[test]
+    fn atomic_write_permissions() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("installed.txt");
+
+        write_atomic(&path, "foo/bar@1.0.0\n")?;
+        assert_eq!(mode(&path)?, 0o644);
+
+        fs_err::set_permissions(&path, Permissions::from_mode(0o664))?;
+        write_atomic(&path, "foo/bar@2.0.0\n")?;
+        assert_eq!(mode(&path)?, 0o664);
+        assert_eq!(fs_err::read_to_string(&path)?, "foo/bar@2.0.0\n");
+
+        write_atomic_private(&path, "secret\n")?;
+        assert_eq!(mode(&path)?, 0o600);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+//! Finds the credential to use for each request Aftman makes.
+//!
+//! Credentials come from, in order of precedence:
+//! 1. Environment variables, which only hold tokens for github.com
+//! 2. auth.toml
+//! 3. The credential helper command set in settings.toml, if there is one
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context};
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::auth::{host_matches, AuthManifest, Credential, CredentialKind, GITHUB_HOST};
+use crate::redact::add_secret;
+
+/// Environment variables that can hold a token for github.com, in order of
+/// precedence.
+pub static GITHUB_TOKEN_VARS: &[&str] = &["AFTMAN_GITHUB_TOKEN", "GITHUB_TOKEN", "GH_TOKEN"];
+
+/// Where a credential came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum CredentialSource {
+    Environment { var: String },
+    AuthToml,
+    Helper,
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Environment { var } => write!(formatter, "the {var} environment variable"),
+            Self::AuthToml => write!(formatter, "auth.toml"),
+            Self::Helper => write!(formatter, "the credential helper"),
+        }
+    }
+}
+
+pub struct CredentialStore {
+    auth: Option<AuthManifest>,
+    env_token: Option<(&'static str, String)>,
+    helper: Vec<String>,
+
+    /// Tokens returned by the credential helper, keyed by host, so that it
+    /// runs at most once per host, even when it has no token for it.
+    helper_tokens: Mutex<BTreeMap<String, Option<String>>>,
+}
+
+impl CredentialStore {
+    pub fn new(auth: Option<AuthManifest>, helper: Vec<String>) -> Self {
+        let env_token = token_from_env(|var| env::var(var).ok());
+
+        if let Some((_, token)) = &env_token {
+            add_secret(token);
+        }
+        if let Some(auth) = &auth {
+            for credential in auth.credentials().values() {
+                add_secret(&credential.token);
+            }
+        }
+
+        Self {
+            auth,
+            env_token,
+            helper,
+            helper_tokens: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Find the credential to use for a request to the given URL.
+    pub fn credential_for(&self, url: &str) -> Option<Credential> {
+        let url = Url::parse(url).ok()?;
+        let (credential, _) = self.find(url.host_str()?)?;
+
+        Some(credential)
+    }
+
+    /// Find the credential to use for requests to the given host, along with
+    /// where it came from.
+    pub fn find(&self, host: &str) -> Option<(Credential, CredentialSource)> {
+        if let Some((var, token)) = &self.env_token {
+            if host_matches(GITHUB_HOST, host) {
+                let credential = Credential {
+                    token: token.clone(),
+                    kind: CredentialKind::GitHub,
+                };
+                let source = CredentialSource::Environment {
+                    var: var.to_string(),
+                };
+
+                return Some((credential, source));
+            }
+        }
+
+        let from_auth = self
+            .auth
+            .as_ref()
+            .and_then(|auth| auth.credential_for(host));
+        if let Some(credential) = from_auth {
+            return Some((credential, CredentialSource::AuthToml));
+        }
+
+        let (helper_host, kind) = self.helper_host(host)?;
+        let token = self.helper_token(&helper_host)?;
+        let credential = Credential { token, kind };
+
+        Some((credential, CredentialSource::Helper))
+    }
+
+    /// Every host with a credential that's known without running the
+    /// credential helper.
+    pub fn known_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = self
+            .auth
+            .as_ref()
+            .map(|auth| auth.credentials().into_keys().collect())
+            .unwrap_or_default();
+
+        if self.env_token.is_some() && !hosts.iter().any(|host| host == GITHUB_HOST) {
+            hosts.insert(0, GITHUB_HOST.to_owned());
+        }
+
+        hosts
+    }
+
+    pub fn has_helper(&self) -> bool {
+        !self.helper.is_empty()
+    }
+
+    /// The host to ask the credential helper about, and the kind of token it
+    /// gives. Helpers like `git credential fill` store credentials under the
+    /// host a user logged in to, so a request to api.github.com has to ask for
+    /// github.com instead.
+    ///
+    /// The helper is only asked about github.com and hosts listed in
+    /// auth.toml, so that it never hands a token to any other server a
+    /// download happens to come from.
+    fn helper_host(&self, host: &str) -> Option<(String, CredentialKind)> {
+        if host_matches(GITHUB_HOST, host) {
+            return Some((GITHUB_HOST.to_owned(), CredentialKind::GitHub));
+        }
+
+        self.auth.as_ref().and_then(|auth| {
+            auth.credentials()
+                .into_iter()
+                .filter(|(pattern, _)| host_matches(pattern, host))
+                .max_by_key(|(pattern, _)| pattern.len())
+                .map(|(pattern, credential)| (pattern, credential.kind))
+        })
+    }
+
+    fn helper_token(&self, host: &str) -> Option<String> {
+        if self.helper.is_empty() {
+            return None;
+        }
+
+        let mut tokens = self.helper_tokens.lock().unwrap();
+        tokens
+            .entry(host.to_owned())
+            .or_insert_with(|| match run_helper(&self.helper, host) {
+                Ok(token) => {
+                    if let Some(token) = &token {
+                        add_secret(token);
+                    }
+                    token
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Could not get a token for {host} from the credential helper: {err:#}"
+                    );
+                    None
+                }
+            })
+            .clone()
+    }
+}
+
+/// The first environment variable that holds a token for github.com, and
+/// that token.
+pub fn token_from_env<F>(get_var: F) -> Option<(&'static str, String)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    GITHUB_TOKEN_VARS.iter().find_map(|var| {
+        let token = get_var(var)?;
+        let token = token.trim();
+
+        if token.is_empty() {
+            None
+        } else {
+            Some((*var, token.to_owned()))
+        }
+    })
+}
+
+/// Ask the credential helper for a token for the given host. Like git
+/// credential helpers, it's given the protocol and host on stdin.
+fn run_helper(command: &[String], host: &str) -> anyhow::Result<Option<String>> {
+    log::debug!("Running credential helper for {host}");
+
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Could not run {}", command[0]))?;
+
+    // Helpers that don't need the request might exit without reading it, so
+    // failing to write it isn't an error.
+    if let Some(mut stdin) = child.stdin.take() {
+        let request = format!("protocol=https\nhost={host}\n\n");
+        if let Err(err) = stdin.write_all(request.as_bytes()) {
+            log::debug!("Could not write to credential helper: {err}");
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{} exited with {}", command[0], output.status);
+    }
+
+    let stdout = String::from_utf8(output.stdout).context("Output was not valid UTF-8")?;
+    Ok(parse_helper_output(&stdout))
+}
+
+/// Helpers can either print only the token, or print `key=value` lines like
+/// `git credential fill` does, in which case the token is the password.
+fn parse_helper_output(output: &str) -> Option<String> {
+    let password = output
+        .lines()
+        .find_map(|line| line.strip_prefix("password="));
+
+    let token = match password {
+        Some(password) => password.trim(),
+        None => {
+            let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+            match (lines.next(), lines.next()) {
+                (Some(line), None) if !line.contains('=') => line.trim(),
+                _ => return None,
+            }
+        }
+    };
+
+    if token.is_empty() {
+        None
+    } else {
+        Some(token.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn store(auth: &str, env_token: Option<(&'static str, &str)>) -> CredentialStore {
+        CredentialStore {
+            auth: Some(toml::from_str(auth).unwrap()),
+            env_token: env_token.map(|(var, token)| (var, token.to_owned())),
+            helper: Vec::new(),
+            helper_tokens: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    #[test]
+    fn env_vars() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |var: &str| {
+                pairs
+                    .iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(token_from_env(vars(&[])), None);
+        assert_eq!(
+            token_from_env(vars(&[("GH_TOKEN", "gh"), ("GITHUB_TOKEN", "actions")])),
+            Some(("GITHUB_TOKEN", "actions".to_owned()))
+        );
+        assert_eq!(
+            token_from_env(vars(&[("AFTMAN_GITHUB_TOKEN", " "), ("GH_TOKEN", "gh")])),
+            Some(("GH_TOKEN", "gh".to_owned()))
+        );
+    }
+
+    #[test]
+    fn env_takes_precedence_for_github() {
+        let store = store(
+            r#"
+            github = "from-file"
+
+            [hosts."ghe.corp.net"]
+            token = "enterprise"
+            "#,
+            Some(("GITHUB_TOKEN", "from-env")),
+        );
+
+        let (credential, source) = store.find("api.github.com").unwrap();
+        assert_eq!(credential.token, "from-env");
+        assert_eq!(
+            source,
+            CredentialSource::Environment {
+                var: "GITHUB_TOKEN".to_owned()
+            }
+        );
+
+        let (credential, source) = store.find("ghe.corp.net").unwrap();
+        assert_eq!(credential.token, "enterprise");
+        assert_eq!(source, CredentialSource::AuthToml);
+
+        assert!(store.credential_for("not a url").is_none());
+        assert_eq!(store.known_hosts(), vec!["ghe.corp.net", "github.com"]);
+    }
+
+    #[test]
+    fn helper_output() {
+        assert_eq!(parse_helper_output("abc\n").as_deref(), Some("abc"));
+        assert_eq!(
+            parse_helper_output("protocol=https\nhost=github.com\nusername=me\npassword=abc\n")
+                .as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            parse_helper_output("protocol=https\nhost=github.com\n"),
+            None
+        );
+        assert_eq!(parse_helper_output("\n"), None);
+        assert_eq!(parse_helper_output("two\nlines\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn helper() {
+        let mut store = store(
+            r#"
+            [hosts."ghe.corp.net"]
+            "#,
+            None,
+        );
+        store.helper = vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            r#"host=$(grep '^host=' | cut -d= -f2); echo "password=token-for-$host""#.to_owned(),
+        ];
+
+        let (credential, source) = store.find("ghe.corp.net").unwrap();
+        assert_eq!(credential.token, "token-for-ghe.corp.net");
+        assert_eq!(source, CredentialSource::Helper);
+
+        let (credential, _) = store.find("api.github.com").unwrap();
+        assert_eq!(credential.token, "token-for-github.com");
+        assert_eq!(
+            store
+                .helper_tokens
+                .lock()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["ghe.corp.net", "github.com"]
+        );
+
+        // Other hosts, like wherever a download redirects to, never get a
+        // token from the helper.
+        assert!(store.find("objects.example.com").is_none());
+        assert_eq!(store.helper_tokens.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn helper_runs_once_without_token() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let count_path = dir.path().join("count");
+
+        let mut store = store("", None);
+        store.helper = vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!("echo run >> '{}'; exit 1", count_path.display()),
+        ];
+
+        assert!(store.find("github.com").is_none());
+        assert!(store.find("api.github.com").is_none());
+        assert_eq!(fs_err::read_to_string(&count_path)?, "run\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::BTreeSet;
+use std::env::{self, consts::EXE_SUFFIX, current_exe};
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::auth::{AuthManifest, GITHUB_HOST};
+use crate::credentials::token_from_env;
+use crate::home::Home;
+use crate::manifest::Manifest;
+use crate::settings::Settings;
+use crate::tool_id::ToolId;
+use crate::tool_storage::{InstalledToolsCache, ToolStorage};
+
+/// How healthy one aspect of an Aftman installation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        };
+
+        formatter.write_str(label)
+    }
+}
+
+/// The result of a single health check.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+
+    /// What the user can do to resolve the problem, if anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+
+    /// Whether the problem was fixed by `aftman doctor --fix`.
+    pub fixed: bool,
+
+    #[serde(skip)]
+    fix: Option<Fix>,
+}
+
+/// Fixes that are safe for Aftman to apply without asking.
+#[derive(Debug, PartialEq, Eq)]
+enum Fix {
+    AddToPath,
+    UpdateLinks,
+    ForgetTools(Vec<ToolId>),
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            message: message.into(),
+            suggestion: None,
+            fixed: false,
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        message: impl Into<String>,
+        suggestion: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+            suggestion: Some(suggestion.into()),
+            fixed: false,
+            fix: None,
+        }
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
+    /// Tells whether `--fix` would be able to resolve this problem.
+    pub fn is_fixable(&self) -> bool {
+        self.fix.is_some()
+    }
+}
+
+impl Fix {
+    fn apply(&self, home: &Home, tools: &ToolStorage) -> anyhow::Result<()> {
+        match self {
+            Fix::AddToPath => {
+                crate::system_path::add(home)?;
+            }
+            Fix::UpdateLinks => tools.update_links()?,
+            Fix::ForgetTools(ids) => {
+                let installed_path = tools.storage_dir.join("installed.txt");
+                InstalledToolsCache::remove(&installed_path, ids)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Apply every available fix. Fixes shared by several checks are only applied
+/// once.
+pub fn fix_all(checks: &mut [Check], home: &Home, tools: &ToolStorage) -> anyhow::Result<()> {
+    let mut applied = Vec::new();
+
+    for check in checks {
+        let fix = match check.fix.take() {
+            Some(fix) => fix,
+            None => continue,
+        };
+
+        if !applied.contains(&fix) {
+            fix.apply(home, tools)
+                .with_context(|| format!("Failed to fix {} check", check.name))?;
+            applied.push(fix);
+        }
+
+        check.fixed = true;
+    }
+
+    Ok(())
+}
+
+/// Run every health check against the Aftman installation in `home`, using
+/// manifests reachable from `current_dir`.
+pub fn run_checks(
+    home: &Home,
+    tools: &ToolStorage,
+    current_dir: &Path,
+) -> anyhow::Result<Vec<Check>> {
+    let (manifests, manifest_check) = check_manifests(home, current_dir);
+
+    Ok(vec![
+        check_path(home),
+        check_aftman_binary(tools)?,
+        check_stale_shims(tools)?,
+        check_unused_shims(tools, &manifests)?,
+        check_installed_tools(tools)?,
+        manifest_check,
+        check_auth(home),
+    ])
+}
+
+fn check_path(home: &Home) -> Check {
+    let bin_dir = home.bin_dir();
+    let path_var = env::var_os("PATH").unwrap_or_default();
+
+    if path_contains(&path_var, &bin_dir) {
+        Check::ok("path", format!("{} is on your PATH", home.bin_dir_str()))
+    } else {
+        Check::problem(
+            "path",
+            Status::Error,
+            format!("{} is not on your PATH", home.bin_dir_str()),
+            "Run `aftman self-install` and restart your terminal.",
+        )
+        .with_fix(Fix::AddToPath)
+    }
+}
+
+fn path_contains(path_var: &OsStr, dir: &Path) -> bool {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+
+    env::split_paths(path_var).any(|entry| {
+        let entry = entry.canonicalize().unwrap_or(entry);
+        entry == dir
+    })
+}
+
+fn check_aftman_binary(tools: &ToolStorage) -> anyhow::Result<Check> {
+    let aftman_path = tools.bin_dir.join(format!("aftman{EXE_SUFFIX}"));
+
+    if !aftman_path.is_file() {
+        return Ok(Check::problem(
+            "aftman_binary",
+            Status::Error,
+            format!("Aftman is not installed in {}", tools.bin_dir.display()),
+            "Run `aftman self-install`.",
+        )
+        .with_fix(Fix::UpdateLinks));
+    }
+
+    if !same_contents(&aftman_path, &current_exe()?)? {
+        return Ok(Check::problem(
+            "aftman_binary",
+            Status::Warn,
+            format!(
+                "{} is a different version of Aftman than the one running",
+                aftman_path.display()
+            ),
+            "Run `aftman self-install` with the version of Aftman you want to use.",
+        )
+        .with_fix(Fix::UpdateLinks));
+    }
+
+    Ok(Check::ok("aftman_binary", "Aftman is installed"))
+}
+
+fn check_stale_shims(tools: &ToolStorage) -> anyhow::Result<Check> {
+    let aftman_path = tools.bin_dir.join(format!("aftman{EXE_SUFFIX}"));
+    let reference = if aftman_path.is_file() {
+        aftman_path
+    } else {
+        current_exe()?
+    };
+
+    let mut stale = Vec::new();
+    for (alias, path) in shims(tools)? {
+        // Symlinks always point at the current Aftman binary.
+        if fs_err::symlink_metadata(&path)?.file_type().is_symlink() {
+            continue;
+        }
+
+        if !same_contents(&path, &reference)? {
+            stale.push(alias);
+        }
+    }
+
+    if stale.is_empty() {
+        return Ok(Check::ok("stale_shims", "All aliases are up to date"));
+    }
+
+    Ok(Check::problem(
+        "stale_shims",
+        Status::Warn,
+        format!(
+            "These aliases use a different version of Aftman: {}",
+            stale.join(", ")
+        ),
+        "Run `aftman self-install` to update them.",
+    )
+    .with_fix(Fix::UpdateLinks))
+}
+
+fn check_unused_shims(tools: &ToolStorage, manifests: &[Manifest]) -> anyhow::Result<Check> {
+    let listed: BTreeSet<&str> = manifests
+        .iter()
+        .flat_map(|manifest| manifest.tools.keys())
+        .map(|alias| alias.as_ref())
+        .collect();
+
+    let unused: Vec<String> = shims(tools)?
+        .into_iter()
+        .map(|(alias, _)| alias)
+        .filter(|alias| !listed.contains(alias.as_str()))
+        .collect();
+
+    if unused.is_empty() {
+        return Ok(Check::ok(
+            "unused_shims",
+            "Every alias is listed by a reachable manifest",
+        ));
+    }
+
+    // Other projects might still use these aliases, so we never remove them
+    // automatically.
+    Ok(Check::problem(
+        "unused_shims",
+        Status::Warn,
+        format!(
+            "No aftman.toml reachable from this directory lists these aliases: {}",
+            unused.join(", ")
+        ),
+        format!(
+            "If no other project uses them, delete them from {}.",
+            tools.bin_dir.display()
+        ),
+    ))
+}
+
+fn check_installed_tools(tools: &ToolStorage) -> anyhow::Result<Check> {
+    let installed_path = tools.storage_dir.join("installed.txt");
+    let installed = InstalledToolsCache::read(&installed_path)?;
+
+    let missing: Vec<ToolId> = installed
+        .tools
+        .into_iter()
+        .filter(|id| !tools.exe_path(id).is_file())
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(Check::ok(
+            "installed_tools",
+            "Every installed tool has an executable",
+        ));
+    }
+
+    let list = missing
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok(Check::problem(
+        "installed_tools",
+        Status::Error,
+        format!("These tools are listed as installed, but their executables are missing: {list}"),
+        "Remove them from installed.txt so that they are installed again on next use.",
+    )
+    .with_fix(Fix::ForgetTools(missing)))
+}
+
+/// Load every manifest reachable from `current_dir`, like
+/// `Manifest::discover`, but report invalid manifests instead of stopping at
+/// the first one.
+fn check_manifests(home: &Home, current_dir: &Path) -> (Vec<Manifest>, Check) {
+    let mut manifests = Vec::new();
+    let mut errors = Vec::new();
+
+    for dir in Manifest::discovery_dirs(home, current_dir) {
+        match Manifest::load_from_dir(dir) {
+            Ok(Some(manifest)) => manifests.push(manifest),
+            Ok(None) => {}
+            Err(err) => errors.push(format!("{err:#}")),
+        }
+    }
+
+    let check = if errors.is_empty() {
+        Check::ok(
+            "manifests",
+            format!("Found {} valid manifest(s)", manifests.len()),
+        )
+    } else {
+        Check::problem(
+            "manifests",
+            Status::Error,
+            errors.join("\n"),
+            "Fix or remove the invalid aftman.toml files.",
+        )
+    };
+
+    (manifests, check)
+}
+
+fn check_auth(home: &Home) -> Check {
+    if let Some((var, _)) = token_from_env(|var| env::var(var).ok()) {
+        return Check::ok("auth", format!("A GitHub token is set by {var}"));
+    }
+
+    let has_helper = Settings::load(home)
+        .map(|settings| !settings.credential_helper.is_empty())
+        .unwrap_or(false);
+
+    match AuthManifest::load(home) {
+        Ok(Some(auth)) if auth.credential_for(GITHUB_HOST).is_some() => {
+            Check::ok("auth", "A GitHub token is configured")
+        }
+        Ok(_) if has_helper => Check::ok("auth", "A credential helper is configured"),
+        Ok(_) => Check::problem(
+            "auth",
+            Status::Warn,
+            "No GitHub token is configured, so downloads are subject to strict rate limits",
+            "Run `aftman auth login` to add a token.",
+        ),
+        Err(err) => Check::problem(
+            "auth",
+            Status::Error,
+            format!("{err:#}"),
+            "Fix the syntax of ~/.aftman/auth.toml.",
+        ),
+    }
+}
+
+/// Every alias in the bin directory and the path to its executable.
+fn shims(tools: &ToolStorage) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let aftman_name = format!("aftman{EXE_SUFFIX}");
+    let mut shims = Vec::new();
+
+    for entry in fs_err::read_dir(&tools.bin_dir)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        if name == aftman_name || name.starts_with('.') {
+            continue;
+        }
+
+        let alias = name.strip_suffix(EXE_SUFFIX).unwrap_or(name).to_owned();
+        shims.push((alias, path));
+    }
+
+    shims.sort();
+    Ok(shims)
+}
+
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs_err::metadata(a)?.len() != fs_err::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut a = io::BufReader::new(fs_err::File::open(a)?);
+    let mut b = io::BufReader::new(fs_err::File::open(b)?);
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn path_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+
+        let path_var = env::join_paths([other.path(), dir.path()]).unwrap();
+        assert!(path_contains(&path_var, dir.path()));
+
+        let path_var = env::join_paths([other.path()]).unwrap();
+        assert!(!path_contains(&path_var, dir.path()));
+    }
+
+    #[test]
+    fn forget_missing_tools() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let tools = ToolStorage::new(&home)?;
+        let installed_path = tools.storage_dir.join("installed.txt");
+
+        let id: ToolId = "foo/bar@1.0.0".parse()?;
+        InstalledToolsCache::add(&installed_path, &id)?;
+
+        let mut check = check_installed_tools(&tools)?;
+        assert_eq!(check.status, Status::Error);
+        assert!(check.is_fixable());
+
+        fix_all(std::slice::from_mut(&mut check), &home, &tools)?;
+        assert!(check.fixed);
+        assert!(!check.is_fixable());
+        assert!(InstalledToolsCache::read(&installed_path)?.tools.is_empty());
+        assert_eq!(check_installed_tools(&tools)?.status, Status::Ok);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_manifests() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let project = tempfile::tempdir()?;
+        let project = project.path();
+        fs_err::write(project.join("aftman.toml"), "[tools]\nrojo = 7\n")?;
+        fs_err::write(
+            home.path().join("aftman.toml"),
+            "[tools]\nlune = \"lune-org/lune@0.8.0\"\n",
+        )?;
+
+        let (manifests, check) = check_manifests(&home, project);
+        assert_eq!(manifests.len(), 1);
+        assert_eq!(check.status, Status::Error);
+        assert!(check.message.contains("Invalid manifest"));
+
+        Ok(())
+    }
+}
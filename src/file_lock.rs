@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use fs2::FileExt;
+use once_cell::sync::Lazy;
+
+/// Paths of the locks held by this process. Threads of a parallel install
+/// wait on each other too, which shouldn't be reported as waiting on another
+/// process.
+static HELD: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Default::default);
+
+/// An advisory lock on a file, used to keep multiple Aftman processes from
+/// mutating the same part of tool storage at the same time.
+///
+/// The lock is released when this value is dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Acquire an exclusive lock on the file at the given path, creating it if
+    /// it doesn't exist. Blocks until any other process holding the lock
+    /// releases it.
+    pub fn acquire(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Could not open lock file {}", path.display()))?;
+
+        if file.try_lock_exclusive().is_err() {
+            if !HELD.lock().unwrap().contains(path) {
+                log::info!("Waiting for another Aftman process to finish...");
+            }
+            log::debug!("Blocked on lock file {}", path.display());
+
+            file.lock_exclusive()
+                .with_context(|| format!("Could not lock {}", path.display()))?;
+        }
+
+        HELD.lock().unwrap().insert(path.to_owned());
+
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        HELD.lock().unwrap().remove(&self.path);
+
+        if let Err(err) = FileExt::unlock(&self.file) {
+            log::debug!("Could not unlock {}: {err}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lock_is_exclusive() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("nested").join("test.lock");
+
+        let lock = FileLock::acquire(&path)?;
+
+        let other = File::open(&path)?;
+        assert!(other.try_lock_exclusive().is_err());
+
+        drop(lock);
+        assert!(other.try_lock_exclusive().is_ok());
+
+        Ok(())
+    }
+}
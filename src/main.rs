@@ -1,21 +1,34 @@
+mod audit;
 mod auth;
 mod cli;
 mod config;
+mod credentials;
 mod dirs;
+mod doctor;
+mod file_lock;
 mod home;
 mod ident;
 mod manifest;
+mod output;
 mod process;
+mod progress;
+mod redact;
+mod resolve;
+mod settings;
+mod shim_cache;
 mod system_path;
 mod tool_alias;
 mod tool_id;
 mod tool_name;
+mod tool_pattern;
 mod tool_source;
 mod tool_spec;
 mod tool_storage;
 mod trust;
 
 use std::env::{consts::EXE_SUFFIX, current_dir, current_exe};
+#[cfg(unix)]
+use std::path::Path;
 
 use anyhow::{bail, format_err, Context};
 use clap::Parser;
@@ -24,22 +37,57 @@ use crate::auth::AuthManifest;
 use crate::cli::Args;
 use crate::home::Home;
 use crate::manifest::Manifest;
+use crate::output::OutputFormat;
+use crate::resolve::{Overrides, Source};
+use crate::settings::Settings;
+use crate::shim_cache::ShimCache;
 use crate::tool_storage::ToolStorage;
 
 fn run() -> anyhow::Result<()> {
     let home = Home::from_env()?;
-    let tool_storage = ToolStorage::new(&home)?;
     let exe_name = current_exe_name()?;
 
     if exe_name != "aftman" {
         let start_dir = current_dir().context("Failed to find current working directory")?;
+        let args = std::env::args().skip(1).collect();
+
+        // Overrides can change from one invocation to the next without any
+        // files changing, so we don't cache resolutions that involve them.
+        let overrides = Overrides::from_env();
+        let use_cache = overrides.is_empty();
+
+        if use_cache {
+            let cache = ShimCache::load(&home);
+            if let Some(id) = cache.get(&start_dir, &exe_name) {
+                let tool_storage = ToolStorage::for_run(&home)?;
+                if tool_storage.exe_path(id).is_file() {
+                    std::process::exit(tool_storage.run_installed(id, args)?);
+                }
+            }
+        }
+
+        let tool_storage = ToolStorage::new(&home)?;
+
+        let files = shim_cache::capture_files(&home, &start_dir);
         let manifests = Manifest::discover(&home, &start_dir)?;
 
-        for manifest in &manifests {
-            if let Some(tool_id) = manifest.tools.get(exe_name.as_str()) {
-                let args = std::env::args().skip(1).collect();
-                std::process::exit(tool_storage.run(tool_id, args)?);
+        if let Some(resolution) = resolve::resolve(&manifests, &overrides, &exe_name)? {
+            let manifest = match &resolution.winner.source {
+                Source::Manifest { path } => path.clone(),
+                Source::Environment { .. } => None,
+            };
+            let id = resolution.winner.id;
+            tool_storage.ensure_installed(&id, manifest.as_deref())?;
+
+            if use_cache {
+                let mut cache = ShimCache::load(&home);
+                cache.insert(&start_dir, &exe_name, id.clone(), files);
+                if let Err(err) = cache.save(&home) {
+                    log::debug!("Could not save shim cache: {err:?}");
+                }
             }
+
+            std::process::exit(tool_storage.run_installed(&id, args)?);
         }
 
         // If we're in Aftman's bin dir, we know for sure that we were supposed
@@ -63,14 +111,48 @@ fn run() -> anyhow::Result<()> {
         }
     }
 
+    // Everything after this point prints errors in the format that was asked
+    // for, so parse arguments before touching any files.
+    let args = parse_args()?;
+    output::set_format(args.format());
+
+    let tool_storage = ToolStorage::new(&home)?;
     Manifest::init_global(&home)?;
     AuthManifest::init(&home)?;
+    Settings::init(&home)?;
     system_path::init(&home)?;
 
-    Args::from_args().run(&home, tool_storage)
+    args.run(&home, tool_storage)
+}
+
+/// Parse command line arguments. When JSON output was asked for, usage errors
+/// are returned like any other error so that they're printed as JSON too.
+fn parse_args() -> anyhow::Result<Args> {
+    match Args::try_parse() {
+        Ok(args) => Ok(args),
+        Err(err) => {
+            // Help and version output are also reported as errors by clap.
+            if err.use_stderr() && output::json_requested(std::env::args().skip(1)) {
+                output::set_format(OutputFormat::Json);
+                return Err(err.into());
+            }
+
+            err.exit()
+        }
+    }
 }
 
 fn current_exe_name() -> anyhow::Result<String> {
+    // On Unix, aliases are symlinks to Aftman. The path to the current
+    // executable has those symlinks resolved, so we use the name we were
+    // invoked with instead.
+    #[cfg(unix)]
+    if let Some(arg0) = std::env::args_os().next() {
+        if let Some(name) = Path::new(&arg0).file_name().and_then(|name| name.to_str()) {
+            return Ok(name.to_owned());
+        }
+    }
+
     let exe_path = current_exe().context("Failed to discover the name of the Aftman executable")?;
     let mut exe_name = exe_path
         .file_name()
@@ -89,13 +171,24 @@ fn current_exe_name() -> anyhow::Result<String> {
 fn main() {
     let log_env = env_logger::Env::default().default_filter_or("info");
 
-    env_logger::Builder::from_env(log_env)
+    let logger = env_logger::Builder::from_env(log_env)
         .format_module_path(false)
         .format_timestamp(None)
-        .init();
+        .build();
+
+    progress::init_logger(logger);
 
     if let Err(err) = run() {
-        eprintln!("Aftman error: {:?}", err);
+        match output::format() {
+            OutputFormat::Text => {
+                eprintln!("Aftman error: {}", redact::redact(&format!("{:?}", err)))
+            }
+            OutputFormat::Json => {
+                let error = output::ErrorOutput::new(&err);
+                eprintln!("{}", serde_json::to_string(&error).unwrap());
+            }
+        }
+
         std::process::exit(1);
     }
 }
@@ -6,7 +6,7 @@ use anyhow::{bail, format_err, Context};
 use serde::{Deserialize, Serialize};
 use toml_edit::Document;
 
-use crate::config::{write_if_not_exists, write_only_new};
+use crate::config::{write_atomic, write_if_not_exists, write_only_new};
 use crate::home::Home;
 use crate::tool_alias::ToolAlias;
 use crate::tool_id::ToolId;
@@ -35,7 +35,7 @@ impl Manifest {
     /// Create an empty global Aftman manifest if there isn't one already.
     pub fn init_global(home: &Home) -> anyhow::Result<()> {
         let base_dir = home.path();
-        fs_err::create_dir_all(&base_dir)?;
+        fs_err::create_dir_all(base_dir)?;
 
         let manifest_path = base_dir.join(MANIFEST_FILE_NAME);
         write_if_not_exists(&manifest_path, DEFAULT_MANIFEST.trim())?;
@@ -52,31 +52,29 @@ impl Manifest {
 
     /// Find and load all manifests from the current directory, sorted in
     /// priority order.
-    pub fn discover(home: &Home, mut current_dir: &Path) -> anyhow::Result<Vec<Manifest>> {
+    pub fn discover(home: &Home, current_dir: &Path) -> anyhow::Result<Vec<Manifest>> {
         let mut manifests = Vec::new();
 
-        // Starting in the current directory, find every manifest file,
-        // prioritizing manifests that are closer to our current dir.
-        loop {
-            if let Some(manifest) = Self::load_from_dir(current_dir)? {
+        for dir in Self::discovery_dirs(home, current_dir) {
+            if let Some(manifest) = Self::load_from_dir(dir)? {
                 manifests.push(manifest);
             }
-
-            match current_dir.parent() {
-                Some(parent) => current_dir = parent,
-                None => break,
-            }
-        }
-
-        // We'll also load the user's global config, usually from
-        // ~/.aftman/aftman.toml.
-        if let Some(manifest) = Self::load_from_dir(home.path())? {
-            manifests.push(manifest);
         }
 
         Ok(manifests)
     }
 
+    /// Every directory that manifests are discovered in, in order of priority.
+    /// Starting in the current directory, manifests that are closer to it come
+    /// first, followed by the user's global config, usually from
+    /// ~/.aftman/aftman.toml.
+    pub fn discovery_dirs<'a>(
+        home: &'a Home,
+        current_dir: &'a Path,
+    ) -> impl Iterator<Item = &'a Path> {
+        current_dir.ancestors().chain([home.path()])
+    }
+
     /// Try to load an Aftman manifest from a directory containing an
     /// aftman.toml file.
     pub fn load_from_dir(path: &Path) -> anyhow::Result<Option<Manifest>> {
@@ -146,7 +144,7 @@ impl Manifest {
         let mut document: Document = content.parse()?;
         document["tools"][alias.as_ref()] = toml_edit::value(id.to_string());
 
-        fs_err::write(manifest_path, document.to_string())?;
+        write_atomic(manifest_path, &document.to_string())?;
 
         log::info!(
             "Tool {alias} = {id} has been added to {}",
@@ -8,4 +8,4 @@ pub use windows::run;
 mod unix;
 
 #[cfg(unix)]
-pub use unix::run;
+pub use unix::{exec, run};
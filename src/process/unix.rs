@@ -1,27 +1,65 @@
-//! On Unix, we use tokio to spawn processes so that we can listen for signals
-//! and wait for process completion at the same time.
+//! On Unix, we normally replace the current process with the tool using
+//! `exec`. That keeps signals, job control, and exit statuses working exactly as
+//! if the tool had been run directly.
+//!
+//! When tools are supervised instead, we use tokio to spawn processes so that we
+//! can listen for signals and wait for process completion at the same time.
 
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::Path;
 use std::thread;
+use std::time::Duration;
 
 use anyhow::Context;
-use signal_hook::consts::signal::{SIGABRT, SIGINT, SIGQUIT, SIGTERM};
-use signal_hook::iterator::Signals;
+use signal_hook::consts::signal::{
+    SIGABRT, SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2, SIGWINCH,
+};
+use signal_hook::iterator::exfiltrator::origin::WithOrigin;
+use signal_hook::iterator::SignalsInfo;
+use signal_hook::low_level::siginfo::{Cause, Origin};
 use tokio::process::Command;
-use tokio::sync::oneshot;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
 
-pub fn run(exe_path: &Path, args: Vec<String>) -> anyhow::Result<i32> {
-    let (kill_tx, kill_rx) = oneshot::channel();
+/// Signals that we relay to the tool when we receive them.
+const FORWARDED_SIGNALS: &[i32] = &[
+    SIGABRT, SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2, SIGWINCH,
+];
+
+/// Signals that ask the tool to exit. If the tool is still running after the
+/// grace period, we kill it.
+///
+/// SIGINT is deliberately left out so that interactive tools can handle Ctrl-C
+/// however they like.
+const TERMINATING_SIGNALS: &[i32] = &[SIGHUP, SIGQUIT, SIGTERM];
+
+/// If the tool is killed by one of these signals, we kill ourselves with the
+/// same signal so that our parent sees the same exit status. Shells use this to
+/// decide whether to stop running a script after Ctrl-C.
+const REPRODUCED_SIGNALS: &[i32] = &[SIGHUP, SIGINT, SIGTERM];
+
+/// Replace the current process with the given executable. This only returns if
+/// the executable could not be started.
+pub fn exec(exe_path: &Path, args: Vec<String>) -> anyhow::Error {
+    let err = std::process::Command::new(exe_path).args(args).exec();
+    anyhow::Error::new(err).context(format!("could not exec {}", exe_path.display()))
+}
+
+pub fn run(exe_path: &Path, args: Vec<String>, kill_grace_period: Duration) -> anyhow::Result<i32> {
+    let (signal_tx, mut signal_rx) = mpsc::unbounded_channel();
 
     // Spawn a thread dedicated to listening for signals and relaying them to
     // our async runtime.
     let (signal_thread, signal_handle) = {
-        let mut signals = Signals::new(&[SIGABRT, SIGINT, SIGQUIT, SIGTERM]).unwrap();
+        let mut signals = SignalsInfo::<WithOrigin>::new(FORWARDED_SIGNALS)
+            .context("could not register signal handlers")?;
         let signal_handle = signals.handle();
 
         let thread = thread::spawn(move || {
-            if let Some(signal) = signals.into_iter().next() {
-                kill_tx.send(signal).ok();
+            for origin in &mut signals {
+                if signal_tx.send(origin).is_err() {
+                    break;
+                }
             }
         });
 
@@ -30,6 +68,7 @@ pub fn run(exe_path: &Path, args: Vec<String>) -> anyhow::Result<i32> {
 
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_io()
+        .enable_time()
         .build()
         .context("could not create tokio runtime")?;
 
@@ -40,28 +79,88 @@ pub fn run(exe_path: &Path, args: Vec<String>) -> anyhow::Result<i32> {
         .spawn()
         .with_context(|| format!("could not spawn {}", exe_path.display()))?;
 
-    let code = runtime.block_on(async move {
-        tokio::select! {
-            // If the child exits cleanly, we can return its exit code directly.
-            // I wish everything were this tidy.
-            status = child.wait() => {
-                let code = status.ok().and_then(|s| s.code()).unwrap_or(1);
-                signal_handle.close();
-                signal_thread.join().unwrap();
+    let pid = child.id();
 
-                code
-            }
+    let status = runtime.block_on(async {
+        let mut kill_deadline = None;
 
-            // If we received a signal while the process was running, murder it
-            // and exit immediately with the correct error code.
-            code = kill_rx => {
-                child.kill().await.ok();
-                signal_handle.close();
-                signal_thread.join().unwrap();
-                std::process::exit(128 + code.unwrap_or(0));
+        loop {
+            tokio::select! {
+                // If the child exits, we can return its exit status directly.
+                // I wish everything were this tidy.
+                status = child.wait() => break status,
+
+                // Relay signals we receive to the child, and start the clock
+                // on killing it if we were asked to exit.
+                Some(origin) = signal_rx.recv() => {
+                    if let Some(pid) = pid {
+                        forward_signal(pid, &origin);
+                    }
+
+                    if TERMINATING_SIGNALS.contains(&origin.signal) && kill_deadline.is_none() {
+                        kill_deadline = Some(Instant::now() + kill_grace_period);
+                    }
+                }
+
+                // The tool had its chance to shut down cleanly, but didn't.
+                _ = wait_until(kill_deadline) => {
+                    log::warn!(
+                        "Tool did not exit within {} seconds, killing it.",
+                        kill_grace_period.as_secs()
+                    );
+
+                    child.start_kill().ok();
+                    kill_deadline = None;
+                }
             }
         }
     });
 
-    Ok(code)
+    signal_handle.close();
+    signal_thread.join().unwrap();
+
+    let status = status.context("could not wait for tool to exit")?;
+
+    if let Some(code) = status.code() {
+        return Ok(code);
+    }
+
+    match status.signal() {
+        Some(signal) => {
+            if REPRODUCED_SIGNALS.contains(&signal) {
+                signal_hook::low_level::emulate_default_handler(signal).ok();
+            }
+
+            Ok(128 + signal)
+        }
+        None => Ok(1),
+    }
+}
+
+/// Waits until the given deadline, or forever if there is no deadline.
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Relay a signal we received to the tool.
+///
+/// Signals that come from the kernel, like Ctrl-C or resizing the terminal, are
+/// already delivered to the whole foreground process group, which includes the
+/// tool. We only relay signals that were sent to us specifically so that the
+/// tool doesn't receive them twice.
+fn forward_signal(pid: u32, origin: &Origin) {
+    if !matches!(origin.cause, Cause::Sent(_)) {
+        return;
+    }
+
+    log::debug!("Forwarding signal {} to tool", origin.signal);
+
+    // SAFETY: kill has no memory safety requirements. At worst, the process
+    // has already exited and this fails.
+    unsafe {
+        libc::kill(pid as libc::pid_t, origin.signal);
+    }
 }
@@ -3,11 +3,16 @@
 
 use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
 
 use anyhow::Context;
 use command_group::CommandGroup;
 
-pub fn run(exe_path: &Path, args: Vec<String>) -> anyhow::Result<i32> {
+pub fn run(
+    exe_path: &Path,
+    args: Vec<String>,
+    _kill_grace_period: Duration,
+) -> anyhow::Result<i32> {
     // On Windows, using a job group here will cause the subprocess to terminate
     // automatically when Aftman is terminated.
     let mut child = Command::new(exe_path)
@@ -0,0 +1,84 @@
+//! Progress reporting for long-running operations like downloads.
+//!
+//! Progress bars are only drawn when stderr is a terminal. Log output is routed
+//! through the same draw target so that log lines don't tear through bars that
+//! are currently being displayed.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{Log, Metadata, Record};
+use once_cell::sync::Lazy;
+
+use crate::redact::redact;
+
+static PROGRESS: Lazy<MultiProgress> = Lazy::new(|| {
+    let target = if atty::is(atty::Stream::Stderr) {
+        ProgressDrawTarget::stderr()
+    } else {
+        ProgressDrawTarget::hidden()
+    };
+
+    MultiProgress::with_draw_target(target)
+});
+
+/// Install the given logger as the global logger, suspending any visible
+/// progress bars while each message is written.
+pub fn init_logger(logger: env_logger::Logger) {
+    let max_level = logger.filter();
+
+    log::set_boxed_logger(Box::new(ProgressLogger { inner: logger }))
+        .expect("logger was already initialized");
+    log::set_max_level(max_level);
+}
+
+/// Create a progress bar for downloading a file with the given label and
+/// length, if the length is known.
+pub fn download_bar(label: &str, len: Option<u64>) -> ProgressBar {
+    let bar = match len {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(
+                "{msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner} {msg} {bytes} ({bytes_per_sec})").unwrap(),
+        ),
+    };
+
+    PROGRESS.add(bar.with_message(label.to_owned()))
+}
+
+struct ProgressLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for ProgressLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            // Messages at any level might include secrets, like a request's
+            // headers or a signed download URL.
+            let message = redact(&record.args().to_string());
+
+            PROGRESS.suspend(|| {
+                self.inner.log(
+                    &Record::builder()
+                        .args(format_args!("{}", message))
+                        .metadata(record.metadata().clone())
+                        .module_path(record.module_path())
+                        .file(record.file())
+                        .line(record.line())
+                        .build(),
+                )
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
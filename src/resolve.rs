@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::{format_err, Context};
+use semver::Version;
+use serde::Serialize;
+
+use crate::manifest::Manifest;
+use crate::tool_id::ToolId;
+
+/// Environment variable that overrides aliases with specific tools, like
+/// `rojo=rojo-rbx/rojo@7.4.0,lune=lune-org/lune@0.8.0`.
+pub static OVERRIDE_VAR: &str = "AFTMAN_OVERRIDE";
+
+/// A single place where an alias was defined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Definition {
+    pub id: ToolId,
+
+    #[serde(flatten)]
+    pub source: Source,
+}
+
+/// Where a definition came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Source {
+    Manifest { path: Option<PathBuf> },
+    Environment { var: String },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Manifest { path: Some(path) } => write!(formatter, "{}", path.display()),
+            Self::Manifest { path: None } => write!(formatter, "an unknown manifest"),
+            Self::Environment { var } => write!(formatter, "the {var} environment variable"),
+        }
+    }
+}
+
+/// Describes how a tool alias resolves to a specific tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct Resolution {
+    /// The definition that will be used when running the alias.
+    pub winner: Definition,
+
+    /// Definitions with lower priority that were ignored because of the
+    /// winning definition.
+    pub shadowed: Vec<Definition>,
+}
+
+/// Per-invocation overrides for alias resolution, read from environment
+/// variables. These take precedence over every manifest.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    vars: BTreeMap<String, String>,
+}
+
+impl Overrides {
+    pub fn from_env() -> Self {
+        Self::from_vars(env::vars())
+    }
+
+    pub fn from_vars<I, K, V>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .filter(|(key, _)| key == OVERRIDE_VAR || is_version_var(key))
+            .collect();
+
+        Self { vars }
+    }
+
+    /// Tells whether no overrides are set at all.
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Find an override for the given alias. `base` is the tool that the alias
+    /// would resolve to without any overrides, which version overrides apply
+    /// to.
+    fn get(&self, alias: &str, base: Option<&ToolId>) -> anyhow::Result<Option<Definition>> {
+        if let Some(value) = self.vars.get(OVERRIDE_VAR) {
+            for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (entry_alias, id) = entry.split_once('=').ok_or_else(|| {
+                    format_err!("Invalid {OVERRIDE_VAR} entry \"{entry}\". It must be of the form ALIAS=SCOPE/NAME@VERSION.")
+                })?;
+
+                if entry_alias.trim() == alias {
+                    let id = id
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("Invalid {OVERRIDE_VAR} entry \"{entry}\""))?;
+
+                    return Ok(Some(Definition {
+                        id,
+                        source: Source::Environment {
+                            var: OVERRIDE_VAR.to_owned(),
+                        },
+                    }));
+                }
+            }
+        }
+
+        let var = version_var(alias);
+        if let (Some(value), Some(base)) = (self.vars.get(&var), base) {
+            let version: Version = value
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid version in {var}"))?;
+
+            return Ok(Some(Definition {
+                id: ToolId::new(base.name().clone(), version),
+                source: Source::Environment { var },
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Tells whether the given variable looks like a version override, which needs
+/// an alias between the prefix and suffix. `AFTMAN_VERSION` isn't one.
+fn is_version_var(key: &str) -> bool {
+    key.strip_prefix("AFTMAN_")
+        .and_then(|rest| rest.strip_suffix("_VERSION"))
+        .map_or(false, |alias| !alias.is_empty())
+}
+
+/// The environment variable that overrides the version of the given alias, like
+/// `AFTMAN_ROJO_VERSION` for `rojo`.
+pub fn version_var(alias: &str) -> String {
+    let alias: String = alias
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("AFTMAN_{alias}_VERSION")
+}
+
+/// Resolve the given alias using a list of manifests sorted in priority order,
+/// like the list returned by `Manifest::discover`, and any overrides.
+pub fn resolve(
+    manifests: &[Manifest],
+    overrides: &Overrides,
+    alias: &str,
+) -> anyhow::Result<Option<Resolution>> {
+    let mut definitions: Vec<Definition> = manifests
+        .iter()
+        .filter_map(|manifest| {
+            manifest.tools.get(alias).map(|id| Definition {
+                id: id.clone(),
+                source: Source::Manifest {
+                    path: manifest.path.clone(),
+                },
+            })
+        })
+        .collect();
+
+    let base = definitions.first().map(|definition| &definition.id);
+    if let Some(definition) = overrides.get(alias, base)? {
+        definitions.insert(0, definition);
+    }
+
+    if definitions.is_empty() {
+        return Ok(None);
+    }
+
+    let winner = definitions.remove(0);
+
+    Ok(Some(Resolution {
+        winner,
+        shadowed: definitions,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest(path: &str, tools: &[(&str, &str)]) -> Manifest {
+        Manifest {
+            tools: tools
+                .iter()
+                .map(|(alias, id)| (alias.parse().unwrap(), id.parse().unwrap()))
+                .collect(),
+            path: Some(PathBuf::from(path)),
+        }
+    }
+
+    fn manifests() -> Vec<Manifest> {
+        vec![
+            manifest("project/aftman.toml", &[("rojo", "rojo-rbx/rojo@6.2.0")]),
+            manifest("aftman.toml", &[]),
+            manifest(
+                "home/aftman.toml",
+                &[
+                    ("rojo", "rojo-rbx/rojo@7.5.1"),
+                    ("lune", "lune-org/lune@0.8.0"),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn closest_manifest_wins() -> anyhow::Result<()> {
+        let manifests = manifests();
+        let overrides = Overrides::default();
+
+        let resolution = resolve(&manifests, &overrides, "rojo")?.unwrap();
+        assert_eq!(resolution.winner.id.to_string(), "rojo-rbx/rojo@6.2.0");
+        assert_eq!(
+            resolution.winner.source,
+            Source::Manifest {
+                path: Some(PathBuf::from("project/aftman.toml"))
+            }
+        );
+        assert_eq!(resolution.shadowed.len(), 1);
+        assert_eq!(resolution.shadowed[0].id.to_string(), "rojo-rbx/rojo@7.5.1");
+
+        let resolution = resolve(&manifests, &overrides, "lune")?.unwrap();
+        assert!(resolution.shadowed.is_empty());
+
+        assert!(resolve(&manifests, &overrides, "selene")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn version_override() -> anyhow::Result<()> {
+        let manifests = manifests();
+        let overrides = Overrides::from_vars([("AFTMAN_ROJO_VERSION", "7.4.0")]);
+
+        let resolution = resolve(&manifests, &overrides, "rojo")?.unwrap();
+        assert_eq!(resolution.winner.id.to_string(), "rojo-rbx/rojo@7.4.0");
+        assert_eq!(
+            resolution.winner.source,
+            Source::Environment {
+                var: "AFTMAN_ROJO_VERSION".to_owned()
+            }
+        );
+        assert_eq!(resolution.shadowed.len(), 2);
+
+        // Version overrides need a manifest to tell them which tool to use.
+        let overrides = Overrides::from_vars([("AFTMAN_SELENE_VERSION", "1.0.0")]);
+        assert!(resolve(&manifests, &overrides, "selene")?.is_none());
+
+        let overrides = Overrides::from_vars([("AFTMAN_ROJO_VERSION", "seven")]);
+        assert!(resolve(&manifests, &overrides, "rojo").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tool_override() -> anyhow::Result<()> {
+        let manifests = manifests();
+        let overrides = Overrides::from_vars([
+            (
+                "AFTMAN_OVERRIDE",
+                "rojo=rojo-rbx/rojo@7.4.0, selene=Kampfkarren/selene@0.27.1",
+            ),
+            ("AFTMAN_ROJO_VERSION", "7.0.0"),
+        ]);
+
+        let resolution = resolve(&manifests, &overrides, "rojo")?.unwrap();
+        assert_eq!(resolution.winner.id.to_string(), "rojo-rbx/rojo@7.4.0");
+        assert_eq!(resolution.shadowed.len(), 2);
+
+        let resolution = resolve(&manifests, &overrides, "selene")?.unwrap();
+        assert_eq!(
+            resolution.winner.id.to_string(),
+            "Kampfkarren/selene@0.27.1"
+        );
+
+        let overrides = Overrides::from_vars([("AFTMAN_OVERRIDE", "rojo")]);
+        assert!(resolve(&manifests, &overrides, "rojo").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unrelated_vars_are_not_overrides() {
+        let overrides = Overrides::from_vars([("AFTMAN_ROOT", "/aftman"), ("PATH", "/bin")]);
+        assert!(overrides.is_empty());
+
+        let overrides = Overrides::from_vars([("AFTMAN_VERSION", "0.3.0")]);
+        assert!(overrides.is_empty());
+
+        let overrides = Overrides::from_vars([("AFTMAN_ROJO_VERSION", "7.4.0")]);
+        assert!(!overrides.is_empty());
+    }
+
+    #[test]
+    fn version_var_names() {
+        assert_eq!(version_var("rojo"), "AFTMAN_ROJO_VERSION");
+        assert_eq!(
+            version_var("wally-package-types"),
+            "AFTMAN_WALLY_PACKAGE_TYPES_VERSION"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{bail, format_err, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::config::write_if_not_exists;
+use crate::home::Home;
+
+pub static SETTINGS_FILE_NAME: &str = "settings.toml";
+
+static DEFAULT_SETTINGS: &str = r#"
+# This file contains settings for Aftman, a cross-platform toolchain manager.
+# For more information, see https://github.com/LPGhatguy/aftman
+
+# On Unix, Aftman replaces itself with the tools that it runs. Set this to true
+# to run tools as a child process of Aftman instead.
+# supervise_tools = false
+
+# When tools are supervised, how many seconds to give a tool to exit after
+# asking it to before killing it.
+# kill_grace_period = 10
+
+# Check every tool against the hash recorded when it was installed before
+# running it. This protects against corrupted or tampered executables at the
+# cost of reading the whole executable on every run.
+# verify_before_run = false
+
+# Trust policy files listing tools or patterns of tools like "our-org/*" that
+# should be trusted without prompting. Relative paths are relative to this
+# directory.
+# trust_policies = []
+
+# Trust tools one version range at a time. When you trust a tool, Aftman
+# records the range of versions compatible with the one being installed, like
+# ^7 for 7.4.0, and asks again before installing a version outside of it.
+# strict_trust = false
+
+# A command that prints a token for a host, used when neither auth.toml nor an
+# environment variable has one, like ["git", "credential", "fill"]. It's given
+# the host on stdin in the same format as git credential helpers, and can
+# print either the token alone or git-style output with the token as the
+# password. It's only asked about github.com and hosts listed in auth.toml.
+# credential_helper = []
+"#;
+
+/// User-wide settings, stored in ~/.aftman/settings.toml.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Run tools as child processes instead of replacing Aftman with them.
+    /// This has no effect on Windows, which always uses child processes.
+    pub supervise_tools: bool,
+
+    /// The number of seconds a supervised tool has to exit after receiving a
+    /// terminating signal before it is killed.
+    pub kill_grace_period: u64,
+
+    /// Hash tool executables and compare them to their recorded hashes
+    /// before every run.
+    pub verify_before_run: bool,
+
+    /// Paths to trust policy files, relative to the Aftman home.
+    pub trust_policies: Vec<PathBuf>,
+
+    /// Record and enforce version ranges when trusting tools.
+    pub strict_trust: bool,
+
+    /// A command and its arguments to run to get tokens for hosts that have
+    /// no other credentials.
+    pub credential_helper: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            supervise_tools: false,
+            kill_grace_period: 10,
+            verify_before_run: false,
+            trust_policies: Vec::new(),
+            strict_trust: false,
+            credential_helper: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Create a default settings file if there isn't one already.
+    pub fn init(home: &Home) -> anyhow::Result<()> {
+        let base_dir = home.path();
+        fs_err::create_dir_all(base_dir)?;
+
+        let settings_path = base_dir.join(SETTINGS_FILE_NAME);
+        write_if_not_exists(&settings_path, DEFAULT_SETTINGS.trim())?;
+
+        Ok(())
+    }
+
+    /// Load settings from the Aftman home, using the defaults if there is no
+    /// settings file.
+    pub fn load(home: &Home) -> anyhow::Result<Self> {
+        let file_path = home.path().join(SETTINGS_FILE_NAME);
+
+        let contents = match fs_err::read(&file_path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                if err.kind() == io::ErrorKind::NotFound {
+                    return Ok(Self::default());
+                }
+
+                bail!(err);
+            }
+        };
+
+        let settings = toml::from_slice(&contents)
+            .with_context(|| format_err!("Invalid settings.toml at {}", file_path.display()))?;
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_settings_parse() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        Settings::init(&home)?;
+
+        let settings = Settings::load(&home)?;
+        assert!(!settings.supervise_tools);
+        assert_eq!(settings.kill_grace_period, 10);
+        assert!(!settings.verify_before_run);
+        assert!(settings.trust_policies.is_empty());
+        assert!(!settings.strict_trust);
+        assert!(settings.credential_helper.is_empty());
+
+        Ok(())
+    }
+}
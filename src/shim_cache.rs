@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::write_atomic;
+use crate::home::Home;
+use crate::manifest::MANIFEST_FILE_NAME;
+use crate::tool_id::ToolId;
+
+static CACHE_FILE_NAME: &str = "shim-cache.json";
+
+/// How many resolutions to remember. Once the cache is full, the oldest entry
+/// is dropped.
+const MAX_ENTRIES: usize = 128;
+
+/// Remembers which tool an alias resolved to in a given directory so that
+/// shims can skip reading every manifest on each invocation.
+///
+/// Every entry records the state of each file that could have affected its
+/// resolution: all manifests that were or could have been discovered, and the
+/// list of installed tools. If any of those files change, appear or disappear,
+/// the entry is ignored.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ShimCache {
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    cwd: PathBuf,
+    alias: String,
+    id: ToolId,
+    files: Vec<(PathBuf, Option<FileStamp>)>,
+}
+
+/// Enough information about a file to notice when it changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// The state of every file that a resolution from `cwd` depends on.
+///
+/// This should be captured before reading any manifests, so that changes made
+/// while we resolve an alias invalidate the result.
+pub fn capture_files(home: &Home, cwd: &Path) -> Vec<(PathBuf, Option<FileStamp>)> {
+    let mut paths: Vec<PathBuf> = cwd
+        .ancestors()
+        .map(|dir| dir.join(MANIFEST_FILE_NAME))
+        .collect();
+    paths.push(home.path().join(MANIFEST_FILE_NAME));
+    paths.push(home.path().join("tool-storage").join("installed.txt"));
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let stamp = FileStamp::read(&path);
+            (path, stamp)
+        })
+        .collect()
+}
+
+impl ShimCache {
+    /// Load the cache. A missing or unreadable cache is treated as empty, since
+    /// it can always be rebuilt.
+    pub fn load(home: &Home) -> Self {
+        let path = home.path().join(CACHE_FILE_NAME);
+
+        let contents = match fs_err::read(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        serde_json::from_slice(&contents).unwrap_or_else(|err| {
+            log::debug!("Ignoring invalid shim cache: {err}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, home: &Home) -> anyhow::Result<()> {
+        let path = home.path().join(CACHE_FILE_NAME);
+        let contents = serde_json::to_string(self)?;
+        write_atomic(&path, &contents)
+    }
+
+    /// Find the tool that `alias` resolved to in `cwd` if none of the files
+    /// that resolution depended on have changed since.
+    pub fn get(&self, cwd: &Path, alias: &str) -> Option<&ToolId> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.cwd == cwd && entry.alias == alias)?;
+
+        let fresh = entry
+            .files
+            .iter()
+            .all(|(path, stamp)| FileStamp::read(path) == *stamp);
+
+        if fresh {
+            Some(&entry.id)
+        } else {
+            log::debug!("Shim cache entry for {alias} is stale");
+            None
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        cwd: &Path,
+        alias: &str,
+        id: ToolId,
+        files: Vec<(PathBuf, Option<FileStamp>)>,
+    ) {
+        self.entries
+            .retain(|entry| !(entry.cwd == cwd && entry.alias == alias));
+
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+
+        self.entries.push(CacheEntry {
+            cwd: cwd.to_owned(),
+            alias: alias.to_owned(),
+            id,
+            files,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn write_manifest(dir: &Path, tools: &str) {
+        fs_err::create_dir_all(dir).unwrap();
+        fs_err::write(dir.join(MANIFEST_FILE_NAME), format!("[tools]\n{tools}")).unwrap();
+    }
+
+    #[test]
+    fn invalidated_by_manifest_changes() {
+        let home = Home::new_temp().unwrap();
+        let project = home.path().join("project");
+        let nested = project.join("src");
+        write_manifest(&project, "rojo = \"rojo-rbx/rojo@7.4.0\"\n");
+        fs_err::create_dir_all(&nested).unwrap();
+
+        let id: ToolId = "rojo-rbx/rojo@7.4.0".parse().unwrap();
+        let mut cache = ShimCache::default();
+        cache.insert(&nested, "rojo", id.clone(), capture_files(&home, &nested));
+        cache.save(&home).unwrap();
+
+        let cache = ShimCache::load(&home);
+        assert_eq!(cache.get(&nested, "rojo"), Some(&id));
+        assert_eq!(cache.get(&nested, "lune"), None);
+        assert_eq!(cache.get(&project, "rojo"), None);
+
+        // A new manifest closer to the working directory.
+        write_manifest(&nested, "");
+        assert_eq!(cache.get(&nested, "rojo"), None);
+        fs_err::remove_file(nested.join(MANIFEST_FILE_NAME)).unwrap();
+        assert_eq!(cache.get(&nested, "rojo"), Some(&id));
+
+        // An edit to the manifest that provided the tool.
+        write_manifest(&project, "rojo = \"rojo-rbx/rojo@7.5.1\"\n");
+        assert_eq!(cache.get(&nested, "rojo"), None);
+    }
+
+    #[test]
+    fn invalidated_by_installs() {
+        let home = Home::new_temp().unwrap();
+        let cwd = home.path().to_owned();
+
+        let id: ToolId = "rojo-rbx/rojo@7.4.0".parse().unwrap();
+        let mut cache = ShimCache::default();
+        cache.insert(&cwd, "rojo", id.clone(), capture_files(&home, &cwd));
+        assert_eq!(cache.get(&cwd, "rojo"), Some(&id));
+
+        let storage_dir = home.path().join("tool-storage");
+        fs_err::create_dir_all(&storage_dir).unwrap();
+        fs_err::write(storage_dir.join("installed.txt"), "rojo-rbx/rojo@7.4.0\n").unwrap();
+        assert_eq!(cache.get(&cwd, "rojo"), None);
+    }
+
+    #[test]
+    fn hits_do_not_read_manifests() {
+        let home = Home::new_temp().unwrap();
+        let project = home.path().join("project");
+        let cwd = project.join("a/b/c");
+        fs_err::create_dir_all(&cwd).unwrap();
+
+        // A manifest that can't be parsed, so a lookup that read it would
+        // fail instead of hitting the cache.
+        write_manifest(&project, "rojo = ");
+        assert!(Manifest::discover(&home, &cwd).is_err());
+
+        let id: ToolId = "rojo-rbx/rojo@7.4.0".parse().unwrap();
+        let mut cache = ShimCache::default();
+        cache.insert(&cwd, "rojo", id.clone(), capture_files(&home, &cwd));
+        cache.save(&home).unwrap();
+
+        let cache = ShimCache::load(&home);
+        assert_eq!(cache.get(&cwd, "rojo"), Some(&id));
+
+        // Once the manifest's stamp changes, the entry can't be used.
+        write_manifest(&project, "rojo = \"rojo-rbx/rojo@7.5.1\"\n");
+        assert_eq!(cache.get(&cwd, "rojo"), None);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{format_err, Context};
+use serde::de::{Deserialize, Deserializer, Error, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::ident::check_ident;
+use crate::tool_name::ToolName;
+
+/// Matches one or more tool names, like `rojo-rbx/rojo` or `rojo-rbx/*`.
+///
+/// A `*` in either the scope or the name matches any sequence of characters
+/// within that part of the name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ToolPattern {
+    inner: String,
+    scope_len: usize,
+}
+
+impl ToolPattern {
+    pub fn scope(&self) -> &str {
+        &self.inner[0..self.scope_len]
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner[(self.scope_len + 1)..]
+    }
+
+    /// Tells whether this pattern matches more than one exact tool name.
+    pub fn is_wildcard(&self) -> bool {
+        self.inner.contains('*')
+    }
+
+    pub fn matches(&self, name: &ToolName) -> bool {
+        glob_matches(self.scope(), name.scope()) && glob_matches(self.name(), name.name())
+    }
+}
+
+/// Matches `value` against a pattern where `*` stands for any sequence of
+/// characters.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // Split always yields at least one part, which is the literal prefix.
+    let prefix = parts.next().unwrap();
+    let mut rest = match value.strip_prefix(prefix) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let suffix = match parts.pop() {
+        Some(suffix) => suffix,
+        // No wildcards, so the prefix must have been the whole value.
+        None => return rest.is_empty(),
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(suffix)
+}
+
+impl From<ToolName> for ToolPattern {
+    fn from(name: ToolName) -> Self {
+        let scope_len = name.scope().len();
+
+        Self {
+            inner: name.as_ref().to_owned(),
+            scope_len,
+        }
+    }
+}
+
+impl AsRef<str> for ToolPattern {
+    fn as_ref(&self) -> &str {
+        self.inner.as_ref()
+    }
+}
+
+impl fmt::Display for ToolPattern {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.inner)
+    }
+}
+
+impl FromStr for ToolPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let context = || {
+            format_err!(
+                "Invalid Tool Pattern \"{}\". It must be of the form SCOPE/NAME or SCOPE/*.",
+                value
+            )
+        };
+
+        let mut scope_rest = value.splitn(2, '/');
+        let scope = scope_rest.next().unwrap();
+
+        let name = scope_rest
+            .next()
+            .ok_or_else(|| format_err!("NAME is missing."))
+            .with_context(context)?;
+
+        check_ident("Scope", scope).with_context(context)?;
+        check_ident("Name", name).with_context(context)?;
+
+        Ok(Self {
+            inner: value.to_owned(),
+            scope_len: scope.len(),
+        })
+    }
+}
+
+impl Serialize for ToolPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.inner)
+    }
+}
+
+impl<'de> Deserialize<'de> for ToolPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(ToolPatternVisitor)
+    }
+}
+
+struct ToolPatternVisitor;
+
+impl<'de> Visitor<'de> for ToolPatternVisitor {
+    type Value = ToolPattern;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a Tool Pattern of the form SCOPE/NAME or SCOPE/*"
+        )
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        value.parse().map_err(|err| E::custom(err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: ToolPattern = pattern.parse().expect("failed to parse ToolPattern");
+        let name: ToolName = name.parse().expect("failed to parse ToolName");
+        pattern.matches(&name)
+    }
+
+    #[test]
+    fn exact() {
+        assert!(matches("rojo-rbx/rojo", "rojo-rbx/rojo"));
+        assert!(!matches("rojo-rbx/rojo", "rojo-rbx/rojo-fork"));
+        assert!(!matches("rojo-rbx/rojo", "someone/rojo"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("our-org/*", "our-org/linter"));
+        assert!(matches("our-org/*", "our-org/formatter"));
+        assert!(!matches("our-org/*", "other-org/linter"));
+        assert!(!matches("our-org/*", "our-org-fork/linter"));
+
+        assert!(matches("*/rojo", "rojo-rbx/rojo"));
+        assert!(matches("*/*", "anyone/anything"));
+
+        assert!(matches("our-org/lint-*", "our-org/lint-lua"));
+        assert!(!matches("our-org/lint-*", "our-org/linter"));
+        assert!(matches("our-org/*-cli", "our-org/deploy-cli"));
+        assert!(matches("our-org/a*b*c", "our-org/aXbYc"));
+        assert!(!matches("our-org/a*b*c", "our-org/aXcYb"));
+    }
+
+    #[test]
+    fn from_name() {
+        let name: ToolName = "rojo-rbx/rojo".parse().unwrap();
+        let pattern = ToolPattern::from(name.clone());
+
+        assert_eq!(pattern, "rojo-rbx/rojo".parse().unwrap());
+        assert_eq!(pattern.scope(), "rojo-rbx");
+        assert_eq!(pattern.name(), "rojo");
+        assert!(!pattern.is_wildcard());
+        assert!(pattern.matches(&name));
+    }
+
+    #[test]
+    fn parse_failure() {
+        assert!("our-org".parse::<ToolPattern>().is_err());
+        assert!("/*".parse::<ToolPattern>().is_err());
+        assert!("our-org/".parse::<ToolPattern>().is_err());
+    }
+}
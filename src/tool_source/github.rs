@@ -1,75 +1,172 @@
-use std::io::{Cursor, Read, Seek};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use reqwest::{
-    blocking::Client,
-    header::{ACCEPT, AUTHORIZATION, USER_AGENT},
+    blocking::{Client, Response},
+    header::{ACCEPT, LOCATION, USER_AGENT},
+    redirect, StatusCode, Url,
 };
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
-use crate::auth::AuthManifest;
+use crate::auth::Credential;
+use crate::credentials::CredentialStore;
+use crate::progress;
 use crate::tool_id::ToolId;
 use crate::tool_name::ToolName;
-use crate::tool_source::Asset;
+use crate::tool_source::{Asset, Download, HashingWriter};
 
 use super::Release;
 
 const APP_NAME: &str = "LPGhatguy/aftman";
 
+/// How many redirects a request follows before giving up, which is the same
+/// as reqwest's default.
+const MAX_REDIRECTS: usize = 10;
+
 pub struct GitHubSource {
     client: Client,
-    token: Option<String>,
+    credentials: CredentialStore,
 }
 
 impl GitHubSource {
-    pub fn new(auth: Option<&AuthManifest>) -> Self {
+    pub fn new(credentials: CredentialStore) -> Self {
         Self {
-            client: Client::new(),
-            token: auth.and_then(|t| t.github.clone()),
+            client: Client::builder()
+                .redirect(redirect::Policy::none())
+                .build()
+                .expect("Could not create HTTP client"),
+            credentials,
         }
     }
 
-    pub fn get_all_releases(&self, name: &ToolName) -> anyhow::Result<Vec<Release>> {
-        let url = format!("https://api.github.com/repos/{}/releases", name);
-        let mut builder = self.client.get(&url).header(USER_AGENT, APP_NAME);
+    pub fn credentials(&self) -> &CredentialStore {
+        &self.credentials
+    }
+
+    /// Send a GET request to the given URL, with the credential configured
+    /// for its host, if there is one.
+    fn get(&self, url: &str, accept: Option<&str>) -> anyhow::Result<Response> {
+        self.get_with(url, self.credentials.credential_for(url).as_ref(), accept)
+    }
+
+    /// Send a GET request to the given URL with a specific credential.
+    ///
+    /// Redirects are followed here instead of by reqwest, which only knows to
+    /// drop the `Authorization` header when a redirect leaves the original
+    /// host. Credentials like GitLab's `PRIVATE-TOKEN` header are only ever
+    /// sent to the host they were meant for.
+    fn get_with(
+        &self,
+        url: &str,
+        credential: Option<&Credential>,
+        accept: Option<&str>,
+    ) -> anyhow::Result<Response> {
+        let mut url = Url::parse(url).with_context(|| format!("Invalid URL {url}"))?;
+        let origin = url.origin();
+
+        for _ in 0..=MAX_REDIRECTS {
+            let mut builder = self.client.get(url.clone()).header(USER_AGENT, APP_NAME);
+            if let Some(accept) = accept {
+                builder = builder.header(ACCEPT, accept);
+            }
+            if let Some(credential) = credential.filter(|_| url.origin() == origin) {
+                let (name, value) = credential.header();
+                builder = builder.header(name, value);
+            }
 
-        if let Some(token) = &self.token {
-            builder = builder.header(AUTHORIZATION, format!("token {}", token));
+            let response = builder.send()?;
+            if !response.status().is_redirection() {
+                return Ok(response);
+            }
+
+            let location = match response.headers().get(LOCATION) {
+                Some(location) => location.to_str()?,
+                None => return Ok(response),
+            };
+            url = url
+                .join(location)
+                .with_context(|| format!("Invalid redirect from {url} to {location}"))?;
         }
 
-        let response_body = builder.send()?.text()?;
+        bail!("Too many redirects while requesting {url}");
+    }
 
-        let gh_releases: Vec<GitHubRelease> = serde_json::from_str(&response_body)
-            .with_context(|| format!("Unexpected GitHub API response: {}", response_body))?;
+    pub fn get_all_releases(&self, name: &ToolName) -> anyhow::Result<Vec<Release>> {
+        let url = format!("https://api.github.com/repos/{}/releases", name);
+        let response = self.get(&url, None)?;
+        let status = response.status();
+        let response_body = response.text()?;
+
+        let gh_releases: Vec<GitHubRelease> =
+            serde_json::from_str(&response_body).with_context(|| {
+                format!(
+                    "Unexpected GitHub API response ({status}): {}",
+                    summarize_response(&response_body)
+                )
+            })?;
 
         let releases: Vec<Release> = gh_releases
             .into_iter()
-            .filter_map(|release| {
-                let stripped = release
-                    .tag_name
-                    .strip_prefix('v')
-                    .unwrap_or(release.tag_name.as_str());
-                let version = stripped.parse::<Version>().ok()?;
-
-                let assets = release
-                    .assets
-                    .into_iter()
-                    .filter(|asset| asset.name.ends_with(".zip"))
-                    .map(|asset| Asset::from_name_url(&asset.name, &asset.url))
-                    .collect();
-
-                Some(Release {
-                    version,
-                    assets,
-                    prerelease: release.prerelease,
-                })
-            })
+            .filter_map(GitHubRelease::into_release)
             .collect();
 
         Ok(releases)
     }
 
+    /// Ask the GitHub API server at `host` about the given credential.
+    pub fn token_info(&self, host: &str, credential: &Credential) -> anyhow::Result<TokenInfo> {
+        let url = api_url(host, "user");
+        let response = self.get_with(&url, Some(credential), None)?;
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            bail!("The token for {host} is invalid or has expired.");
+        }
+        if !status.is_success() {
+            bail!("{host} responded with {status}");
+        }
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        // Fine-grained tokens have no scopes, so they don't send this header.
+        let scopes = header("x-oauth-scopes")
+            .map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|scope| !scope.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rate_limit = match (header("x-ratelimit-limit"), header("x-ratelimit-remaining")) {
+            (Some(limit), Some(remaining)) => Some(RateLimit {
+                limit: limit.parse()?,
+                remaining: remaining.parse()?,
+            }),
+            _ => None,
+        };
+
+        let response_body = response.text()?;
+        let user: GitHubUser = serde_json::from_str(&response_body)
+            .context("Unexpected GitHub API response for the current user")?;
+
+        Ok(TokenInfo {
+            user: user.login,
+            scopes,
+            rate_limit,
+        })
+    }
+
     pub fn get_release(&self, id: &ToolId) -> anyhow::Result<Release> {
         // TODO: Better implementation using individual release API instead of
         // using the release list API.
@@ -82,33 +179,226 @@ impl GitHubSource {
             .with_context(|| format!("Could not find release {}", id))
     }
 
-    pub fn download_asset(&self, url: &str) -> anyhow::Result<impl Read + Seek> {
-        let mut builder = self
-            .client
-            .get(url)
-            .header(USER_AGENT, APP_NAME)
-            .header(ACCEPT, "application/octet-stream");
+    /// Download the asset at the given URL into a temporary file in `temp_dir`,
+    /// showing a progress bar with the given label while the download is
+    /// running.
+    pub fn download_asset(
+        &self,
+        url: &str,
+        label: &str,
+        temp_dir: &Path,
+    ) -> anyhow::Result<Download> {
+        let response = self.get(url, Some("application/octet-stream"))?;
 
-        if let Some(token) = &self.token {
-            builder = builder.header(AUTHORIZATION, format!("token {}", token));
+        // Error responses would otherwise be saved as if they were the asset.
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            bail!(
+                "Could not download {url} ({status}): {}",
+                summarize_response(&body)
+            );
         }
 
-        let response = builder.send()?;
-        let body = response.bytes()?.to_vec();
+        let bar = progress::download_bar(label, response.content_length());
+
+        let mut file = tempfile::Builder::new()
+            .prefix("download-")
+            .tempfile_in(temp_dir)
+            .context("Could not create temporary file for download")?;
+
+        // The artifact is hashed while it's being written so that we only
+        // have to pass over it once.
+        let (size, sha256) = {
+            let mut output = HashingWriter::new(BufWriter::new(file.as_file_mut()));
+            let size = io::copy(&mut bar.wrap_read(response), &mut output)?;
+            output.flush()?;
+
+            (size, output.finish())
+        };
+        bar.finish_and_clear();
+
+        file.seek(SeekFrom::Start(0))?;
 
-        Ok(Cursor::new(body))
+        Ok(Download { file, size, sha256 })
     }
 }
 
+/// A short description of an unexpected response body for error messages.
+/// GitHub's errors are JSON objects with a message, which is used if it's
+/// there. Otherwise, only the start of the body is kept, since the rest could
+/// be arbitrarily large.
+fn summarize_response(body: &str) -> String {
+    const MAX_LEN: usize = 200;
+
+    if let Ok(error) = serde_json::from_str::<GitHubError>(body) {
+        return error.message;
+    }
+
+    let body = body.trim();
+    match body.char_indices().nth(MAX_LEN) {
+        Some((index, _)) => format!("{}...", &body[..index]),
+        None => body.to_owned(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubError {
+    message: String,
+}
+
+/// The URL of an endpoint of the GitHub API for the given host. GitHub
+/// Enterprise servers serve their API under `/api/v3`.
+fn api_url(host: &str, path: &str) -> String {
+    if host == "github.com" {
+        format!("https://api.github.com/{path}")
+    } else {
+        format!("https://{host}/api/v3/{path}")
+    }
+}
+
+/// What a GitHub API server reports about the token used to reach it.
+#[derive(Debug, Serialize)]
+pub struct TokenInfo {
+    pub user: String,
+    pub scopes: Vec<String>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    login: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitHubRelease {
     pub tag_name: String,
-    pub prerelease: bool,
     pub assets: Vec<GitHubReleaseAsset>,
 }
 
+impl GitHubRelease {
+    /// Convert this release into a [`Release`] if its tag is a version. Only
+    /// zip assets are kept, since those are the only ones Aftman can install.
+    pub fn into_release(self) -> Option<Release> {
+        let stripped = self
+            .tag_name
+            .strip_prefix('v')
+            .unwrap_or(self.tag_name.as_str());
+        let version = stripped.parse::<Version>().ok()?;
+
+        let assets = self
+            .assets
+            .into_iter()
+            .filter(|asset| asset.name.ends_with(".zip"))
+            .map(|asset| Asset::from_name_url(&asset.name, &asset.url))
+            .collect();
+
+        Some(Release { version, assets })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitHubReleaseAsset {
     pub url: String,
     pub name: String,
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::auth::CredentialKind;
+
+    /// Serve a single HTTP response on a local port, returning its URL and a
+    /// handle that gives the request's headers once it has been served.
+    fn serve_once(response: impl Into<String>) -> (String, thread::JoinHandle<String>) {
+        let response = response.into();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/asset.zip", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                request.push_str(&line.to_lowercase());
+                line.clear();
+            }
+
+            stream.write_all(response.as_bytes()).unwrap();
+            request
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn summarize_responses() {
+        assert_eq!(
+            summarize_response(r#"{"message": "Bad credentials", "documentation_url": "x"}"#),
+            "Bad credentials"
+        );
+        assert_eq!(summarize_response(" <html> "), "<html>");
+
+        let long = "x".repeat(1000);
+        assert_eq!(summarize_response(&long), format!("{}...", "x".repeat(200)));
+    }
+
+    #[test]
+    fn download_rejects_errors() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let source = GitHubSource::new(CredentialStore::new(None, Vec::new()));
+
+        let (url, _) = serve_once(
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 23\r\nConnection: close\r\n\r\n{\"message\":\"Not Found\"}",
+        );
+        let err = source
+            .download_asset(&url, "asset", temp_dir.path())
+            .unwrap_err();
+        assert!(err.to_string().ends_with("(404 Not Found): Not Found"));
+        assert_eq!(fs_err::read_dir(temp_dir.path())?.count(), 0);
+
+        let (url, _) =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello");
+        let download = source.download_asset(&url, "asset", temp_dir.path())?;
+        assert_eq!(download.size, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn redirects_drop_credentials() -> anyhow::Result<()> {
+        let source = GitHubSource::new(CredentialStore::new(None, Vec::new()));
+        let credential = Credential {
+            token: "gitlab-token".to_owned(),
+            kind: CredentialKind::GitLab,
+        };
+
+        let (target_url, target) =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello");
+        let (url, origin) = serve_once(format!(
+            "HTTP/1.1 302 Found\r\nLocation: {target_url}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ));
+
+        let response = source.get_with(&url, Some(&credential), None)?;
+        assert_eq!(response.text()?, "hello");
+        assert!(origin
+            .join()
+            .unwrap()
+            .contains("private-token: gitlab-token"));
+        assert!(!target.join().unwrap().contains("private-token"));
+
+        Ok(())
+    }
+}
@@ -1,133 +1,352 @@
 mod github;
+mod target;
 
-use std::env::consts::{ARCH, OS};
+use std::io::{self, Write};
+use std::path::Path;
 
 use semver::Version;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
 
-pub use self::github::GitHubSource;
+pub use self::github::{GitHubSource, TokenInfo};
+pub use self::target::Target;
+
+/// An artifact that has been downloaded into a temporary file on disk.
+///
+/// The file is deleted when this value is dropped.
+#[derive(Debug)]
+pub struct Download {
+    pub file: NamedTempFile,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A writer that computes the SHA-256 hash of everything written through it.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The hash of everything written so far, as a lowercase hex string.
+    pub fn finish(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Compute the SHA-256 hash of the file at the given path.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = fs_err::File::open(path)?;
+    let mut writer = HashingWriter::new(io::sink());
+    io::copy(&mut file, &mut writer)?;
+
+    Ok(writer.finish())
+}
 
 #[derive(Debug)]
 pub struct Release {
     pub version: Version,
-    pub prerelease: bool,
     pub assets: Vec<Asset>,
 }
 
+impl Release {
+    /// Returns the assets from this release that can run on the given host,
+    /// best first.
+    pub fn compatible_assets(&self, host: &Target) -> Vec<&Asset> {
+        let mut assets: Vec<&Asset> = self
+            .assets
+            .iter()
+            .filter(|asset| host.runs(&asset.target))
+            .collect();
+
+        // If any assets say which OS or architecture they're for, the ones
+        // that don't are probably something else, like checksums.
+        let any_has_os = assets.iter().any(|asset| asset.target.os.is_some());
+        let any_has_arch = assets.iter().any(|asset| asset.target.arch.is_some());
+        assets.retain(|asset| {
+            (!any_has_os || asset.target.os.is_some())
+                && (!any_has_arch || asset.target.arch.is_some())
+        });
+
+        assets.sort_by_key(|asset| host.rank(&asset.target));
+        assets
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Asset {
     pub name: String,
     pub url: String,
-    pub os: Option<OperatingSystem>,
-    pub arch: Option<Architecture>,
-    pub toolchain: Option<Toolchain>,
+    pub target: Target,
 }
 
 impl Asset {
-    /// Tells whether this asset is definitely compatible with the current host.
-    #[rustfmt::skip]
-    pub fn compatible(&self) -> bool {
-        use OperatingSystem as Os;
-        use Architecture as Arch;
-
-        match (self.os, self.arch) {
-            (Some(Os::Windows), Some(Arch::X64)) => OS == "windows" && ARCH == "x86_64",
-            (Some(Os::Windows), Some(Arch::X86)) => OS == "windows" && (ARCH == "x86_64" || ARCH == "x86"),
-            (Some(Os::MacOS), Some(Arch::Arm64)) => OS == "macos" && ARCH == "aarch64",
-            (Some(Os::MacOS), Some(Arch::X64)) => OS == "macos" && (ARCH == "aarch64" || ARCH == "x86_64"),
-            (Some(Os::Linux), Some(Arch::X64)) => OS == "linux" && ARCH == "x86_64",
-            (Some(Os::Linux), Some(Arch::X86)) => OS == "linux" && (ARCH == "x86_64" || ARCH == "x86"),
-            _ => false,
-        }
-    }
-
     pub fn from_name_url(name: &str, url: &str) -> Self {
-        let match_name = name.to_ascii_lowercase();
-
-        let os = if match_name.contains("windows")
-            || match_name.contains("win32")
-            || match_name.contains("win64")
-        {
-            Some(OperatingSystem::Windows)
-        } else if match_name.contains("macos")
-            || match_name.contains("osx")
-            || match_name.contains("darwin")
-        {
-            Some(OperatingSystem::MacOS)
-        } else if match_name.contains("linux") || match_name.contains("ubuntu") {
-            Some(OperatingSystem::Linux)
-        } else {
-            None
-        };
-
-        let arch = if match_name.contains("x86-64")
-            || match_name.contains("x86_64")
-            || match_name.contains("x64")
-            || match_name.contains("amd64")
-            || match_name.contains("win64")
-        {
-            Some(Architecture::X64)
-        } else if match_name.contains("x86")
-            || match_name.contains("i686")
-            || match_name.contains("win32")
-            || match_name.contains("i386")
-        {
-            Some(Architecture::X86)
-        } else if match_name.contains("aarch64") || match_name.contains("arm64") {
-            Some(Architecture::Arm64)
-        } else if match_name.contains("arm") || match_name.contains("arm32") {
-            Some(Architecture::Arm32)
-        } else {
-            None
-        };
-
-        let toolchain = if match_name.contains("msvc") {
-            Some(Toolchain::Msvc)
-        } else if match_name.contains("musl") {
-            Some(Toolchain::Musl)
-        } else if match_name.contains("gnu") {
-            Some(Toolchain::Gnu)
-        } else {
-            None
-        };
-
         Self {
             name: name.to_owned(),
             url: url.to_owned(),
-            os,
-            arch,
-            toolchain,
+            target: Target::from_asset_name(name),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum OperatingSystem {
-    Windows,
-    MacOS, // aka OS X
-    Linux,
-}
+#[cfg(test)]
+mod test {
+    use super::github::{GitHubRelease, GitHubReleaseAsset};
+    use super::*;
 
-impl OperatingSystem {
-    pub fn compatible(&self) -> bool {
-        match self {
-            Self::Windows => OS == "windows",
-            Self::MacOS => OS == "macos",
-            Self::Linux => OS == "linux",
-        }
+    #[test]
+    fn hashing_writer() -> anyhow::Result<()> {
+        let mut writer = HashingWriter::new(Vec::new());
+        writer.write_all(b"hello")?;
+
+        assert_eq!(writer.inner, b"hello");
+        assert_eq!(
+            writer.finish(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Architecture {
-    Arm64, // aka AArch64
-    X64,   // aka x86-64, AMD64
-    X86,   // aka i686
-    Arm32,
-}
+    /// Build a release the same way releases from GitHub are, so that assets
+    /// Aftman can't install are left out.
+    fn release(names: &[&str]) -> Release {
+        let release = GitHubRelease {
+            tag_name: "v1.0.0".to_owned(),
+            assets: names
+                .iter()
+                .map(|name| GitHubReleaseAsset {
+                    url: String::new(),
+                    name: name.to_string(),
+                })
+                .collect(),
+        };
+
+        release.into_release().unwrap()
+    }
+
+    #[test]
+    fn picks_assets() {
+        let rojo = release(&[
+            "rojo-7.4.1-linux-aarch64.zip",
+            "rojo-7.4.1-linux-x86_64.zip",
+            "rojo-7.4.1-macos-aarch64.zip",
+            "rojo-7.4.1-macos-x86_64.zip",
+            "rojo-7.4.1-windows-x86_64.zip",
+        ]);
+        let selene = release(&[
+            "selene-0.25.0-linux.zip",
+            "selene-0.25.0-macos.zip",
+            "selene-0.25.0-windows.zip",
+            "selene-light-0.25.0-linux.zip",
+        ]);
+        let stylua = release(&[
+            "stylua-linux-aarch64-musl.zip",
+            "stylua-linux-aarch64.zip",
+            "stylua-linux-x86_64-musl.zip",
+            "stylua-linux-x86_64.zip",
+            "stylua-macos-aarch64.zip",
+            "stylua-macos-x86_64.zip",
+            "stylua-win64.zip",
+        ]);
+        let ripgrep = release(&[
+            "ripgrep-14.1.0-aarch64-apple-darwin.zip",
+            "ripgrep-14.1.0-aarch64-unknown-linux-gnu.zip",
+            "ripgrep-14.1.0-armv7-unknown-linux-gnueabihf.zip",
+            "ripgrep-14.1.0-armv7-unknown-linux-musleabi.zip",
+            "ripgrep-14.1.0-armv7-unknown-linux-musleabihf.zip",
+            "ripgrep-14.1.0-i686-pc-windows-msvc.zip",
+            "ripgrep-14.1.0-i686-unknown-linux-gnu.zip",
+            "ripgrep-14.1.0-powerpc64-unknown-linux-gnu.zip",
+            "ripgrep-14.1.0-s390x-unknown-linux-gnu.zip",
+            "ripgrep-14.1.0-x86_64-apple-darwin.zip",
+            "ripgrep-14.1.0-x86_64-pc-windows-gnu.zip",
+            "ripgrep-14.1.0-x86_64-pc-windows-msvc.zip",
+            "ripgrep-14.1.0-x86_64-unknown-linux-musl.zip",
+            "ripgrep-14.1.0-x86_64-unknown-linux-gnu.tar.gz",
+            "ripgrep_14.1.0-1_amd64.deb",
+        ]);
+        let uv = release(&[
+            "uv-aarch64-apple-darwin.zip",
+            "uv-aarch64-pc-windows-msvc.zip",
+            "uv-aarch64-unknown-linux-gnu.zip",
+            "uv-aarch64-unknown-linux-musl.zip",
+            "uv-arm-unknown-linux-musleabihf.zip",
+            "uv-armv7-unknown-linux-gnueabihf.zip",
+            "uv-armv7-unknown-linux-musleabihf.zip",
+            "uv-i686-pc-windows-msvc.zip",
+            "uv-i686-unknown-linux-gnu.zip",
+            "uv-powerpc64le-unknown-linux-gnu.zip",
+            "uv-riscv64gc-unknown-linux-gnu.zip",
+            "uv-x86_64-apple-darwin.zip",
+            "uv-x86_64-pc-windows-msvc.zip",
+            "uv-x86_64-unknown-linux-gnu.zip",
+            "uv-x86_64-unknown-linux-musl.zip",
+            "uv-installer.sh",
+            "sha256.sum",
+        ]);
+        let gh = release(&[
+            "gh_2.40.0_checksums.txt",
+            "gh_2.40.0_linux_386.zip",
+            "gh_2.40.0_linux_amd64.zip",
+            "gh_2.40.0_linux_arm64.zip",
+            "gh_2.40.0_linux_armv6.zip",
+            "gh_2.40.0_macOS_amd64.zip",
+            "gh_2.40.0_macOS_arm64.zip",
+            "gh_2.40.0_windows_386.zip",
+            "gh_2.40.0_windows_amd64.zip",
+            "gh_2.40.0_windows_arm64.zip",
+        ]);
+
+        let cases = [
+            (
+                "x86_64-linux-gnu",
+                &rojo,
+                Some("rojo-7.4.1-linux-x86_64.zip"),
+            ),
+            ("x86_64-linux-gnu", &selene, Some("selene-0.25.0-linux.zip")),
+            ("x86_64-linux-gnu", &stylua, Some("stylua-linux-x86_64.zip")),
+            (
+                "x86_64-linux-gnu",
+                &ripgrep,
+                Some("ripgrep-14.1.0-x86_64-unknown-linux-musl.zip"),
+            ),
+            (
+                "x86_64-linux-gnu",
+                &uv,
+                Some("uv-x86_64-unknown-linux-gnu.zip"),
+            ),
+            ("x86_64-linux-gnu", &gh, Some("gh_2.40.0_linux_amd64.zip")),
+            (
+                "x86_64-linux-musl",
+                &stylua,
+                Some("stylua-linux-x86_64-musl.zip"),
+            ),
+            (
+                "x86_64-linux-musl",
+                &uv,
+                Some("uv-x86_64-unknown-linux-musl.zip"),
+            ),
+            ("i686-linux-gnu", &uv, Some("uv-i686-unknown-linux-gnu.zip")),
+            ("i686-linux-gnu", &rojo, None),
+            (
+                "aarch64-linux-gnu",
+                &rojo,
+                Some("rojo-7.4.1-linux-aarch64.zip"),
+            ),
+            (
+                "aarch64-linux-gnu",
+                &stylua,
+                Some("stylua-linux-aarch64.zip"),
+            ),
+            (
+                "aarch64-linux-gnu",
+                &ripgrep,
+                Some("ripgrep-14.1.0-aarch64-unknown-linux-gnu.zip"),
+            ),
+            ("aarch64-linux-gnu", &gh, Some("gh_2.40.0_linux_arm64.zip")),
+            (
+                "aarch64-linux-musl",
+                &stylua,
+                Some("stylua-linux-aarch64-musl.zip"),
+            ),
+            (
+                "armv7-linux-gnueabihf",
+                &ripgrep,
+                Some("ripgrep-14.1.0-armv7-unknown-linux-gnueabihf.zip"),
+            ),
+            (
+                "armv7-linux-musleabihf",
+                &ripgrep,
+                Some("ripgrep-14.1.0-armv7-unknown-linux-musleabihf.zip"),
+            ),
+            (
+                "armv7-linux-musleabi",
+                &ripgrep,
+                Some("ripgrep-14.1.0-armv7-unknown-linux-musleabi.zip"),
+            ),
+            (
+                "armv7-linux-gnueabihf",
+                &gh,
+                Some("gh_2.40.0_linux_armv6.zip"),
+            ),
+            ("armv7-linux-gnueabihf", &rojo, None),
+            (
+                "arm-linux-gnueabihf",
+                &gh,
+                Some("gh_2.40.0_linux_armv6.zip"),
+            ),
+            ("arm-linux-gnueabihf", &ripgrep, None),
+            (
+                "arm-linux-musleabihf",
+                &uv,
+                Some("uv-arm-unknown-linux-musleabihf.zip"),
+            ),
+            (
+                "riscv64-linux-gnu",
+                &uv,
+                Some("uv-riscv64gc-unknown-linux-gnu.zip"),
+            ),
+            ("riscv64-linux-gnu", &rojo, None),
+            ("riscv64-linux-gnu", &ripgrep, None),
+            ("aarch64-macos", &rojo, Some("rojo-7.4.1-macos-aarch64.zip")),
+            ("aarch64-macos", &selene, Some("selene-0.25.0-macos.zip")),
+            ("aarch64-macos", &gh, Some("gh_2.40.0_macOS_arm64.zip")),
+            (
+                "aarch64-macos",
+                &release(&["tool-macos-x86_64.zip", "tool-linux-aarch64.zip"]),
+                Some("tool-macos-x86_64.zip"),
+            ),
+            ("x86_64-macos", &rojo, Some("rojo-7.4.1-macos-x86_64.zip")),
+            ("x86_64-windows-msvc", &stylua, Some("stylua-win64.zip")),
+            (
+                "x86_64-windows-msvc",
+                &ripgrep,
+                Some("ripgrep-14.1.0-x86_64-pc-windows-msvc.zip"),
+            ),
+            (
+                "x86_64-windows-msvc",
+                &gh,
+                Some("gh_2.40.0_windows_amd64.zip"),
+            ),
+            (
+                "aarch64-windows-msvc",
+                &uv,
+                Some("uv-aarch64-pc-windows-msvc.zip"),
+            ),
+            (
+                "aarch64-windows-msvc",
+                &rojo,
+                Some("rojo-7.4.1-windows-x86_64.zip"),
+            ),
+        ];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Toolchain {
-    Msvc,
-    Gnu,
-    Musl,
+        for (host, release, expected) in cases {
+            let host = Target::from_asset_name(host);
+            let assets = release.compatible_assets(&host);
+            let picked = assets.first().map(|asset| asset.name.as_str());
+
+            assert_eq!(picked, expected, "picking an asset for {host}");
+        }
+    }
 }
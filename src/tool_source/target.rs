@@ -0,0 +1,597 @@
+//! Describes the platforms that binaries are built for, and which of those
+//! platforms can run on the current host.
+//!
+//! A platform is described by a target triple like the ones Rust uses, such as
+//! `armv7-unknown-linux-gnueabihf`: an architecture, an operating system, the C
+//! library or toolchain the binary is linked against, and for 32-bit ARM, the
+//! floating point ABI. Release assets usually only name some of these parts,
+//! so every part of a [`Target`] is optional.
+
+use std::env::consts::{ARCH, OS};
+use std::fmt;
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+
+static HOST: Lazy<Target> = Lazy::new(|| Target::detect_host(|path| Path::new(path).exists()));
+
+/// The dynamic loaders that glibc and musl install on Linux, which tell which
+/// C library and floating point ABI the system uses. glibc's are listed first
+/// since musl is sometimes installed alongside it.
+static LOADERS: &[(Architecture, Toolchain, Option<Abi>, &str)] = {
+    use Abi::*;
+    use Architecture::*;
+    use Toolchain::*;
+
+    &[
+        (X64, Gnu, None, "/lib64/ld-linux-x86-64.so.2"),
+        (X86, Gnu, None, "/lib/ld-linux.so.2"),
+        (Arm64, Gnu, None, "/lib/ld-linux-aarch64.so.1"),
+        (Arm32, Gnu, Some(Eabihf), "/lib/ld-linux-armhf.so.3"),
+        (Arm32, Gnu, Some(Eabi), "/lib/ld-linux.so.3"),
+        (Riscv64, Gnu, None, "/lib/ld-linux-riscv64-lp64d.so.1"),
+        (X64, Musl, None, "/lib/ld-musl-x86_64.so.1"),
+        (X86, Musl, None, "/lib/ld-musl-i386.so.1"),
+        (Arm64, Musl, None, "/lib/ld-musl-aarch64.so.1"),
+        (Arm32, Musl, Some(Eabihf), "/lib/ld-musl-armhf.so.1"),
+        (Arm32, Musl, Some(Eabi), "/lib/ld-musl-arm.so.1"),
+        (Riscv64, Musl, None, "/lib/ld-musl-riscv64.so.1"),
+    ]
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Target {
+    pub arch: Option<Architecture>,
+    pub os: Option<OperatingSystem>,
+    pub toolchain: Option<Toolchain>,
+    pub abi: Option<Abi>,
+}
+
+impl Target {
+    /// The platform Aftman is running on.
+    pub fn host() -> &'static Target {
+        &HOST
+    }
+
+    /// Work out which platform an asset was built for from its name.
+    pub fn from_asset_name(name: &str) -> Self {
+        // Without this, x86_64 would be split into two words.
+        let name = name
+            .to_ascii_lowercase()
+            .replace("x86_64", "x64")
+            .replace("x86-64", "x64");
+
+        let words: Vec<&str> = name
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        // Version numbers can contain words that look like architectures, like
+        // the 386 in 1.386.0. The architecture usually comes after the
+        // version, so the last match wins, and bare numbers only count when
+        // they're next to the operating system.
+        let next_to_os = |index: usize| {
+            let before = index.checked_sub(1).and_then(|index| words.get(index));
+            let after = words.get(index + 1);
+
+            [before, after]
+                .into_iter()
+                .flatten()
+                .any(|word| OperatingSystem::from_word(word).is_some())
+        };
+        let arch = words.iter().enumerate().rev().find_map(|(index, word)| {
+            let arch = Architecture::from_word(word)?;
+            let is_number = word.bytes().all(|byte| byte.is_ascii_digit());
+
+            (!is_number || next_to_os(index)).then_some(arch)
+        });
+
+        Self {
+            arch,
+            os: OperatingSystem::from_words(&words),
+            toolchain: words.iter().find_map(|word| Toolchain::from_word(word)),
+            abi: words.iter().find_map(|word| Abi::from_word(word)),
+        }
+    }
+
+    /// Tells whether a binary built for the given target could run on this
+    /// one. Parts of either target that aren't known are assumed to match.
+    pub fn runs(&self, asset: &Target) -> bool {
+        let os = match (self.os, asset.os) {
+            (Some(host), Some(asset)) if host != asset => return false,
+            (Some(host), _) => Some(host),
+            (None, os) => os,
+        };
+
+        if let (Some(host), Some(asset)) = (self.arch, asset.arch) {
+            if !host.runs(asset, os) {
+                return false;
+            }
+        }
+
+        // Binaries linked against glibc don't run on musl systems, but musl
+        // binaries are almost always static, so they run anywhere.
+        if self.toolchain == Some(Toolchain::Musl) && asset.toolchain == Some(Toolchain::Gnu) {
+            return false;
+        }
+
+        // Hard-float binaries need a hard-float system.
+        if self.abi == Some(Abi::Eabi) && asset.abi == Some(Abi::Eabihf) {
+            return false;
+        }
+
+        true
+    }
+
+    /// How well a runnable asset built for the given target suits this one,
+    /// lowest first. Parts that exactly match the host are best, followed by
+    /// parts that aren't known, and then parts that only run through
+    /// compatibility, like 32-bit x86 binaries on a 64-bit system.
+    pub fn rank(&self, asset: &Target) -> impl Ord {
+        fn part<T: PartialEq + Ord>(host: Option<T>, asset: Option<T>) -> (u8, Option<T>) {
+            match asset {
+                Some(part) if Some(&part) == host.as_ref() => (0, None),
+                None => (1, None),
+                Some(part) => (2, Some(part)),
+            }
+        }
+
+        (
+            part(self.arch, asset.arch),
+            part(self.toolchain, asset.toolchain),
+            part(self.abi, asset.abi),
+        )
+    }
+
+    fn detect_host<F>(exists: F) -> Self
+    where
+        F: Fn(&str) -> bool,
+    {
+        let arch = match ARCH {
+            "x86_64" => Some(Architecture::X64),
+            "x86" => Some(Architecture::X86),
+            "aarch64" => Some(Architecture::Arm64),
+            "arm" if cfg!(target_feature = "v7") => Some(Architecture::Armv7),
+            "arm" => Some(Architecture::Arm32),
+            "riscv64" => Some(Architecture::Riscv64),
+            _ => None,
+        };
+
+        let os = match OS {
+            "windows" => Some(OperatingSystem::Windows),
+            "macos" => Some(OperatingSystem::MacOS),
+            "linux" => Some(OperatingSystem::Linux),
+            _ => None,
+        };
+
+        let mut host = Self {
+            arch,
+            os,
+            toolchain: None,
+            abi: None,
+        };
+
+        if cfg!(target_env = "msvc") {
+            host.toolchain = Some(Toolchain::Msvc);
+        } else if cfg!(target_env = "gnu") {
+            host.toolchain = Some(Toolchain::Gnu);
+        } else if cfg!(target_env = "musl") {
+            host.toolchain = Some(Toolchain::Musl);
+        }
+
+        if cfg!(target_abi = "eabihf") {
+            host.abi = Some(Abi::Eabihf);
+        } else if cfg!(target_abi = "eabi") {
+            host.abi = Some(Abi::Eabi);
+        }
+
+        // Aftman itself might be built for a different C library than the
+        // rest of the system uses, like a static musl build running on a
+        // glibc distro, so prefer checking what's installed.
+        if os == Some(OperatingSystem::Linux) {
+            if let Some((toolchain, abi)) = arch.and_then(|arch| detect_libc(arch, exists)) {
+                host.toolchain = Some(toolchain);
+                host.abi = abi;
+            }
+        }
+
+        host
+    }
+}
+
+/// Find which C library and ABI a Linux system uses from the dynamic loaders
+/// it has.
+fn detect_libc<F>(arch: Architecture, exists: F) -> Option<(Toolchain, Option<Abi>)>
+where
+    F: Fn(&str) -> bool,
+{
+    let arch = match arch {
+        Architecture::Armv7 => Architecture::Arm32,
+        arch => arch,
+    };
+
+    LOADERS
+        .iter()
+        .find(|(loader_arch, _, _, path)| *loader_arch == arch && exists(path))
+        .map(|(_, toolchain, abi, _)| (*toolchain, *abi))
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.arch {
+            Some(arch) => write!(formatter, "{arch}")?,
+            None => write!(formatter, "unknown")?,
+        }
+
+        match self.os {
+            Some(os) => write!(formatter, "-{os}")?,
+            None => write!(formatter, "-unknown")?,
+        }
+
+        if self.toolchain.is_some() || self.abi.is_some() {
+            write!(formatter, "-")?;
+        }
+        if let Some(toolchain) = self.toolchain {
+            write!(formatter, "{toolchain}")?;
+        }
+        if let Some(abi) = self.abi {
+            write!(formatter, "{abi}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OperatingSystem {
+    Windows,
+    MacOS, // aka OS X
+    Linux,
+
+    /// An operating system Aftman doesn't run on, like FreeBSD or Android.
+    Other,
+}
+
+impl OperatingSystem {
+    fn from_words(words: &[&str]) -> Option<Self> {
+        // These are checked first since Android assets usually say "linux" too.
+        const OTHER: &[&str] = &[
+            "android",
+            "dragonfly",
+            "freebsd",
+            "illumos",
+            "ios",
+            "netbsd",
+            "openbsd",
+            "solaris",
+        ];
+
+        if words.iter().any(|word| OTHER.contains(word)) {
+            return Some(Self::Other);
+        }
+
+        words.iter().find_map(|word| Self::from_word(word))
+    }
+
+    fn from_word(word: &str) -> Option<Self> {
+        // Words like "linux64" and "win32" name the architecture too.
+        if word.starts_with("windows") || matches!(word, "win" | "win32" | "win64") {
+            Some(Self::Windows)
+        } else if word.starts_with("macos")
+            || word.starts_with("osx")
+            || word.starts_with("darwin")
+            || matches!(word, "mac" | "apple")
+        {
+            Some(Self::MacOS)
+        } else if word.starts_with("linux") || word.starts_with("ubuntu") {
+            Some(Self::Linux)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for OperatingSystem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Windows => "windows",
+            Self::MacOS => "macos",
+            Self::Linux => "linux",
+            Self::Other => "other",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Architecture {
+    Arm64, // aka AArch64
+    X64,   // aka x86-64, AMD64
+    X86,   // aka i686
+    Armv7,
+
+    /// 32-bit ARM that doesn't need ARMv7, like ARMv6 or ARM with no version.
+    Arm32,
+
+    Riscv64,
+
+    /// An architecture Aftman doesn't run on, like PowerPC or s390x.
+    Other,
+}
+
+impl Architecture {
+    fn from_word(word: &str) -> Option<Self> {
+        match word {
+            "aarch64" | "arm64" => Some(Self::Arm64),
+            "x64" | "amd64" | "win64" | "linux64" => Some(Self::X64),
+            "x86" | "i386" | "i686" | "386" | "ia32" | "win32" | "linux32" => Some(Self::X86),
+            "riscv64" | "riscv64gc" => Some(Self::Riscv64),
+            "powerpc" | "powerpc64" | "powerpc64le" | "ppc64" | "ppc64le" | "s390x" | "mips"
+            | "mipsel" | "mips64" | "mips64el" | "loongarch64" | "sparc64" => Some(Self::Other),
+            // armv8l is a 32-bit system running on a 64-bit CPU, so it's
+            // unclear which kind of binary it means.
+            "armv8l" => None,
+            _ if word.starts_with("armv8") || word.starts_with("armv9") => Some(Self::Arm64),
+            _ if word.starts_with("armv7") => Some(Self::Armv7),
+            "arm" | "arm32" | "armhf" | "armel" => Some(Self::Arm32),
+            _ if word.starts_with("armv") => Some(Self::Arm32),
+            _ => None,
+        }
+    }
+
+    /// Tells whether a host with this architecture can run binaries built for
+    /// the given one.
+    fn runs(self, asset: Self, os: Option<OperatingSystem>) -> bool {
+        use OperatingSystem as Os;
+
+        match (os, self, asset) {
+            (_, Self::Other, _) | (_, _, Self::Other) => false,
+            (_, host, asset) if host == asset => true,
+
+            // 64-bit x86 can run 32-bit x86, except on macOS, which dropped
+            // support for it.
+            (Some(Os::Windows | Os::Linux), Self::X64, Self::X86) => true,
+
+            // Through Rosetta 2.
+            (Some(Os::MacOS), Self::Arm64, Self::X64) => true,
+
+            // Windows on ARM emulates x86 and x86-64.
+            (Some(Os::Windows), Self::Arm64, Self::X64 | Self::X86) => true,
+
+            (_, Self::Armv7, Self::Arm32) => true,
+
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Arm64 => "aarch64",
+            Self::X64 => "x86_64",
+            Self::X86 => "i686",
+            Self::Armv7 => "armv7",
+            Self::Arm32 => "arm",
+            Self::Riscv64 => "riscv64",
+            Self::Other => "other",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Toolchain {
+    Msvc,
+    Gnu,
+    Musl,
+}
+
+impl Toolchain {
+    fn from_word(word: &str) -> Option<Self> {
+        if word == "msvc" {
+            Some(Self::Msvc)
+        } else if word.starts_with("musl") {
+            Some(Self::Musl)
+        } else if word.starts_with("gnu") || word == "glibc" {
+            Some(Self::Gnu)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Toolchain {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Msvc => "msvc",
+            Self::Gnu => "gnu",
+            Self::Musl => "musl",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// The floating point ABI of a 32-bit ARM binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Abi {
+    /// Soft-float, aka armel
+    Eabi,
+
+    /// Hard-float, aka armhf
+    Eabihf,
+}
+
+impl Abi {
+    fn from_word(word: &str) -> Option<Self> {
+        if word.ends_with("eabihf") || word == "armhf" || word == "armv7hf" {
+            Some(Self::Eabihf)
+        } else if word.ends_with("eabi") || word == "armel" {
+            Some(Self::Eabi)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Abi {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Eabi => "eabi",
+            Self::Eabihf => "eabihf",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn asset_names() {
+        let cases = [
+            ("rojo-7.4.1-linux-x86_64.zip", "x86_64-linux"),
+            ("rojo-7.4.1-linux-aarch64.zip", "aarch64-linux"),
+            ("rojo-7.4.1-macos-aarch64.zip", "aarch64-macos"),
+            ("rojo-7.4.1-windows-x86_64.zip", "x86_64-windows"),
+            ("selene-0.25.0-linux.zip", "unknown-linux"),
+            ("stylua-linux-x86_64-musl.zip", "x86_64-linux-musl"),
+            ("stylua-win64.zip", "x86_64-windows"),
+            (
+                "ripgrep-14.1.0-armv7-unknown-linux-gnueabihf.zip",
+                "armv7-linux-gnueabihf",
+            ),
+            (
+                "ripgrep-14.1.0-armv7-unknown-linux-musleabi.zip",
+                "armv7-linux-musleabi",
+            ),
+            (
+                "ripgrep-14.1.0-i686-pc-windows-msvc.zip",
+                "i686-windows-msvc",
+            ),
+            ("ripgrep-14.1.0-x86_64-apple-darwin.zip", "x86_64-macos"),
+            (
+                "ripgrep-14.1.0-powerpc64-unknown-linux-gnu.zip",
+                "other-linux-gnu",
+            ),
+            ("uv-riscv64gc-unknown-linux-gnu.zip", "riscv64-linux-gnu"),
+            (
+                "uv-arm-unknown-linux-musleabihf.zip",
+                "arm-linux-musleabihf",
+            ),
+            ("gh_2.40.0_linux_armv6.zip", "arm-linux"),
+            ("tool-linux-armv8.zip", "aarch64-linux"),
+            ("tool-linux-armv8a.zip", "aarch64-linux"),
+            ("tool-linux-armv8l.zip", "unknown-linux"),
+            ("gh_2.40.0_linux_386.zip", "i686-linux"),
+            ("tool-1.386.0-linux-arm64.zip", "aarch64-linux"),
+            ("tool-2.386.1-linux.zip", "unknown-linux"),
+            ("gh_2.40.0_macOS_amd64.zip", "x86_64-macos"),
+            ("tool-aarch64-linux-android.zip", "aarch64-other"),
+            ("tool-x86_64-unknown-freebsd.zip", "x86_64-other"),
+            ("tool-linux64.zip", "x86_64-linux"),
+            ("tool-armhf.deb", "arm-unknown-eabihf"),
+            ("swarm-1.0.0.zip", "unknown-unknown"),
+            ("checksums.txt", "unknown-unknown"),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                Target::from_asset_name(name).to_string(),
+                expected,
+                "parsing {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compatibility() {
+        let cases = [
+            ("x86_64-linux-gnu", "x86_64-linux-musl", true),
+            ("x86_64-linux-gnu", "i686-linux-gnu", true),
+            ("x86_64-linux-gnu", "aarch64-linux-gnu", false),
+            ("x86_64-linux-musl", "x86_64-linux-gnu", false),
+            ("x86_64-linux-musl", "x86_64-linux", true),
+            ("aarch64-linux-gnu", "aarch64-linux", true),
+            ("aarch64-linux-gnu", "x86_64-linux", false),
+            ("aarch64-linux-gnu", "armv7-linux-gnueabihf", false),
+            ("armv7-linux-gnueabihf", "armv7-linux-gnueabihf", true),
+            ("armv7-linux-gnueabihf", "arm-linux-gnueabihf", true),
+            ("armv7-linux-gnueabihf", "armv7-linux-musleabi", true),
+            ("arm-linux-gnueabihf", "armv7-linux-gnueabihf", false),
+            ("arm-linux-gnueabi", "arm-linux-gnueabihf", false),
+            ("riscv64-linux-gnu", "riscv64-linux-gnu", true),
+            ("riscv64-linux-gnu", "powerpc64le-linux-gnu", false),
+            ("aarch64-macos", "x86_64-macos", true),
+            ("x86_64-macos", "aarch64-macos", false),
+            ("x86_64-macos", "i686-macos", false),
+            ("aarch64-windows-msvc", "x86_64-windows-msvc", true),
+            ("x86_64-windows-msvc", "x86_64-linux", false),
+            ("x86_64-linux-gnu", "x86_64-unknown-freebsd", false),
+        ];
+
+        for (host, asset, expected) in cases {
+            let host_target = Target::from_asset_name(host);
+            let asset_target = Target::from_asset_name(asset);
+            assert_eq!(
+                host_target.runs(&asset_target),
+                expected,
+                "running {asset} on {host}"
+            );
+        }
+    }
+
+    #[test]
+    fn libc_detection() {
+        let cases: &[(Architecture, &[&str], Option<&str>)] = &[
+            (
+                Architecture::X64,
+                &["/lib64/ld-linux-x86-64.so.2"],
+                Some("gnu"),
+            ),
+            (
+                Architecture::X64,
+                &["/lib/ld-musl-x86_64.so.1"],
+                Some("musl"),
+            ),
+            (
+                Architecture::X64,
+                &["/lib/ld-musl-x86_64.so.1", "/lib64/ld-linux-x86-64.so.2"],
+                Some("gnu"),
+            ),
+            (
+                Architecture::Armv7,
+                &["/lib/ld-linux-armhf.so.3"],
+                Some("gnueabihf"),
+            ),
+            (
+                Architecture::Arm32,
+                &["/lib/ld-musl-arm.so.1"],
+                Some("musleabi"),
+            ),
+            (
+                Architecture::Riscv64,
+                &["/lib/ld-linux-riscv64-lp64d.so.1"],
+                Some("gnu"),
+            ),
+            (Architecture::Arm64, &["/lib64/ld-linux-x86-64.so.2"], None),
+        ];
+
+        for (arch, files, expected) in cases {
+            let detected =
+                detect_libc(*arch, |path| files.contains(&path)).map(|(toolchain, abi)| {
+                    format!(
+                        "{toolchain}{}",
+                        abi.map(|abi| abi.to_string()).unwrap_or_default()
+                    )
+                });
+
+            assert_eq!(detected.as_deref(), *expected, "detecting {files:?}");
+        }
+    }
+}
@@ -1,50 +1,88 @@
 use std::borrow::Cow;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env::current_dir;
 use std::env::{consts::EXE_SUFFIX, current_exe};
-use std::fmt::Write;
+use std::fmt::{self, Write};
 use std::io::{self, BufWriter, Read};
 use std::io::{Seek, Write as _};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{bail, Context};
-use fs_err::File;
 use itertools::{Either, Itertools};
-use once_cell::unsync::OnceCell;
+use once_cell::sync::OnceCell;
+use semver::{Version, VersionReq};
+use serde::Serialize;
 
+use crate::audit::{AuditEvent, AuditLog};
 use crate::auth::AuthManifest;
+use crate::config::write_atomic;
+use crate::credentials::CredentialStore;
+use crate::file_lock::FileLock;
 use crate::home::Home;
 use crate::manifest::Manifest;
+use crate::settings::Settings;
 use crate::tool_alias::ToolAlias;
 use crate::tool_id::ToolId;
 use crate::tool_name::ToolName;
-use crate::tool_source::{Asset, GitHubSource, Release};
+use crate::tool_source::{sha256_file, Download, GitHubSource, HashingWriter, Release, Target};
 use crate::tool_spec::ToolSpec;
-use crate::trust::{TrustCache, TrustMode, TrustStatus};
+use crate::trust::{
+    default_range, TrustCache, TrustEntry, TrustError, TrustMode, TrustPolicy, TrustRule,
+    TrustStatus,
+};
+
+/// How old a file in the staging directory needs to be before we assume that
+/// the install that created it was interrupted.
+const STALE_STAGING_AGE: Duration = Duration::from_secs(60 * 60);
 
 pub struct ToolStorage {
     pub storage_dir: PathBuf,
     pub bin_dir: PathBuf,
     home: Home,
     auth: Option<AuthManifest>,
+    settings: Settings,
     github: OnceCell<GitHubSource>,
 }
 
 impl ToolStorage {
     pub fn new(home: &Home) -> anyhow::Result<Self> {
         let storage_dir = home.path().join("tool-storage");
-        fs_err::create_dir_all(&storage_dir)?;
+        fs_err::create_dir_all(storage_dir.join(".staging"))?;
 
         let bin_dir = home.path().join("bin");
         fs_err::create_dir_all(&bin_dir)?;
 
         let auth = AuthManifest::load(home)?;
+        let settings = Settings::load(home)?;
 
-        Ok(Self {
+        let storage = Self {
             storage_dir,
             bin_dir,
             home: home.clone(),
             auth,
+            settings,
+            github: OnceCell::new(),
+        };
+
+        storage.clean_staging_dir()?;
+
+        Ok(storage)
+    }
+
+    /// Open tool storage only to run tools that are already installed, which
+    /// shims do on every invocation. Unlike `new`, this doesn't load auth.toml
+    /// or clean up the staging directory, so it must not be used to install
+    /// anything.
+    pub fn for_run(home: &Home) -> anyhow::Result<Self> {
+        Ok(Self {
+            storage_dir: home.path().join("tool-storage"),
+            bin_dir: home.path().join("bin"),
+            home: home.clone(),
+            auth: None,
+            settings: Settings::load(home)?,
             github: OnceCell::new(),
         })
     }
@@ -54,7 +92,7 @@ impl ToolStorage {
         spec: &ToolSpec,
         alias: Option<&ToolAlias>,
         global: bool,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<ToolId> {
         let current_dir = current_dir().context("Failed to find current working directory")?;
 
         let alias = match alias {
@@ -62,7 +100,7 @@ impl ToolStorage {
             None => Cow::Owned(ToolAlias::new(spec.name().name())?),
         };
 
-        let id = self.install_inexact(spec, TrustMode::Check)?;
+        let id = self.install_inexact(spec, &TrustMode::Check, None)?;
         self.link(&alias)?;
 
         if global {
@@ -71,14 +109,52 @@ impl ToolStorage {
             Manifest::add_local_tool(&self.home, &current_dir, &alias, &id)?;
         }
 
-        Ok(())
+        Ok(id)
     }
 
     pub fn run(&self, id: &ToolId, args: Vec<String>) -> anyhow::Result<i32> {
-        self.install_exact(id, TrustMode::Check)?;
+        self.ensure_installed(id, None)?;
+        self.run_installed(id, args)
+    }
+
+    /// Install the given tool if it isn't installed yet, asking the user to
+    /// trust it if necessary. The manifest that listed the tool, if any, is
+    /// recorded in the audit log.
+    pub fn ensure_installed(&self, id: &ToolId, manifest: Option<&Path>) -> anyhow::Result<()> {
+        self.install_exact(id, &TrustMode::Check, manifest)?;
+        Ok(())
+    }
+
+    /// Run a tool that is already known to be installed.
+    pub fn run_installed(&self, id: &ToolId, args: Vec<String>) -> anyhow::Result<i32> {
+        if self.settings.verify_before_run {
+            let status = self.verify(id)?;
+            if status.is_failure() {
+                bail!(
+                    "Tool {id} failed verification: {status}.\n\
+                    Run `aftman verify --repair {id}` to reinstall it."
+                );
+            }
+            if status == VerifyStatus::Unrecorded {
+                log::warn!(
+                    "Tool {id} has no recorded hash, so it can't be verified. \
+                    Run `aftman verify --record {id}` to record its current hash."
+                );
+            }
+        }
 
         let exe_path = self.exe_path(id);
-        let code = crate::process::run(&exe_path, args).with_context(|| {
+
+        #[cfg(unix)]
+        if !self.settings.supervise_tools {
+            let err = crate::process::exec(&exe_path, args);
+            return Err(err).with_context(|| {
+                format!("Failed to run tool {id}, your installation may be corrupt.")
+            });
+        }
+
+        let kill_grace_period = Duration::from_secs(self.settings.kill_grace_period);
+        let code = crate::process::run(&exe_path, args, kill_grace_period).with_context(|| {
             format!("Failed to run tool {id}, your installation may be corrupt.")
         })?;
         Ok(code)
@@ -87,11 +163,71 @@ impl ToolStorage {
     /// Update all executables managed by Aftman, which might include Aftman
     /// itself.
     pub fn update_links(&self) -> anyhow::Result<()> {
+        log::info!("Updating all Aftman binaries...");
+
+        #[cfg(unix)]
+        self.update_symlinks()?;
+
+        #[cfg(not(unix))]
+        self.update_copies()?;
+
+        log::info!("Updated Aftman binaries successfully!");
+
+        Ok(())
+    }
+
+    /// On Unix, every alias is a symlink to the Aftman executable in the bin
+    /// directory, so that executable is the only file we need to replace.
+    /// Aliases left over from older versions of Aftman, which copied the
+    /// executable, are turned into symlinks.
+    #[cfg(unix)]
+    fn update_symlinks(&self) -> anyhow::Result<()> {
         let self_path =
             current_exe().context("Failed to discover path to the Aftman executable")?;
-        let self_name = self_path.file_name().unwrap();
+        let aftman_path = self.bin_dir.join("aftman");
 
-        log::info!("Updating all Aftman binaries...");
+        if aftman_path.exists() {
+            log::debug!("Updating aftman");
+        } else {
+            log::info!("Installing Aftman...");
+        }
+
+        // Copy our executable next to its destination and rename it into
+        // place, which works even if the old executable is running.
+        let staged = tempfile::Builder::new()
+            .prefix(".aftman-")
+            .tempfile_in(&self.bin_dir)?;
+        fs_err::copy(&self_path, staged.path())?;
+        staged
+            .persist(&aftman_path)
+            .context("Failed to replace Aftman executable")?;
+
+        for entry in fs_err::read_dir(&self.bin_dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+
+            if name == "aftman" || name.starts_with('.') {
+                continue;
+            }
+
+            if fs_err::symlink_metadata(&path)?.file_type().is_symlink() {
+                continue;
+            }
+
+            log::debug!("Replacing {:?} with a link", name);
+            self.symlink_to_aftman(&path)?;
+        }
+
+        Ok(())
+    }
+
+    /// On platforms without symlinks, every alias is a full copy of the Aftman
+    /// executable that needs to be replaced.
+    #[cfg(not(unix))]
+    fn update_copies(&self) -> anyhow::Result<()> {
+        let self_path =
+            current_exe().context("Failed to discover path to the Aftman executable")?;
+        let self_name = self_path.file_name().unwrap();
 
         // Copy our current executable into a temp directory. That way, if it
         // ends up replaced by this process, we'll still have the file that
@@ -130,60 +266,142 @@ impl ToolStorage {
             fs_err::copy(&self_path, aftman_path)?;
         }
 
-        log::info!("Updated Aftman binaries successfully!");
-
         Ok(())
     }
 
-    /// Install all tools from all reachable manifest files.
-    pub fn install_all(&self, trust: TrustMode, skip_untrusted: bool) -> anyhow::Result<()> {
+    /// Install all tools from all reachable manifest files, running up to
+    /// `jobs` installs at the same time.
+    pub fn install_all(
+        &self,
+        trust: TrustMode,
+        skip_untrusted: bool,
+        jobs: usize,
+    ) -> anyhow::Result<InstallSummary> {
         let current_dir = current_dir().context("Failed to get current working directory")?;
         let manifests = Manifest::discover(&self.home, &current_dir)?;
 
         // Installing all tools is split into multiple steps:
         // 1. Trust check, which may prompt the user and yield if untrusted
-        // 2. Installation of trusted tools, which will be in parallel in the future
-        // 3. Reporting of installation trust errors, unless trust errors are skipped
+        // 2. Installation of trusted tools, in parallel
+        // 3. Reporting of installation failures and trust errors, unless
+        //    trust errors are skipped
 
         let (trusted_tools, trust_errors): (Vec<_>, Vec<_>) = manifests
             .iter()
-            .flat_map(|manifest| &manifest.tools)
-            .partition_map(
-                |(alias, tool_id)| match self.trust_check(tool_id.name(), trust) {
-                    Ok(_) => Either::Left((alias, tool_id)),
-                    Err(e) => Either::Right(e),
-                },
-            );
+            .flat_map(|manifest| {
+                let path = manifest.path.as_deref();
+                manifest.tools.iter().map(move |tool| (tool, path))
+            })
+            .partition_map(|((alias, tool_id), path)| {
+                match self.trust_check(tool_id.name(), Some(tool_id.version()), &trust, path) {
+                    Ok(_) => Either::Left((alias, tool_id, path)),
+                    Err(e) => Either::Right((tool_id.clone(), e)),
+                }
+            });
+
+        // Multiple aliases can refer to the same tool, which we only want to
+        // install once. The highest priority manifest that lists a tool is
+        // the one recorded as having caused its install.
+        let mut to_install: BTreeMap<&ToolId, Option<&Path>> = BTreeMap::new();
+        for (_, tool_id, path) in &trusted_tools {
+            to_install.entry(*tool_id).or_insert(*path);
+        }
+        let to_install: Vec<(&ToolId, Option<&Path>)> = to_install.into_iter().collect();
+
+        let results = self.install_many(&to_install, &trust, jobs);
+
+        let mut summary = InstallSummary {
+            tools: BTreeMap::new(),
+            failures: Vec::new(),
+            trust_errors: Vec::new(),
+            skip_untrusted,
+        };
 
-        for (alias, tool_id) in &trusted_tools {
-            self.install_exact(tool_id, trust)?;
-            self.link(alias)?;
+        for (id, result) in results {
+            match result {
+                Ok(status) => {
+                    summary.tools.insert(id, status);
+                }
+                Err(err) => {
+                    summary.tools.insert(id.clone(), InstallStatus::Failed);
+                    summary.failures.push((id, err));
+                }
+            }
         }
 
-        if !trust_errors.is_empty() && !skip_untrusted {
-            bail!(
-                "Installation trust check failed for the following tools:\n{}",
-                trust_errors.iter().map(|e| format!("    {e}")).join("\n")
-            )
+        for (id, err) in trust_errors {
+            summary.tools.insert(id.clone(), InstallStatus::Untrusted);
+            summary.trust_errors.push((id, err));
         }
 
-        Ok(())
+        for (alias, tool_id, _) in &trusted_tools {
+            if summary.tools.get(*tool_id) != Some(&InstallStatus::Failed) {
+                self.link(alias)?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Install the given tools using a pool of up to `jobs` worker threads.
+    /// Each tool is paired with the manifest that listed it.
+    fn install_many(
+        &self,
+        ids: &[(&ToolId, Option<&Path>)],
+        trust: &TrustMode,
+        jobs: usize,
+    ) -> Vec<(ToolId, anyhow::Result<InstallStatus>)> {
+        let total = ids.len();
+        let queue = Mutex::new(ids.iter());
+        let results = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, total.max(1)) {
+                scope.spawn(|| loop {
+                    let (id, manifest) = match queue.lock().unwrap().next() {
+                        Some(next) => *next,
+                        None => break,
+                    };
+
+                    let result = self.install_exact(id, trust, manifest);
+
+                    let mut results = results.lock().unwrap();
+                    let finished = results.len() + 1;
+
+                    match &result {
+                        Ok(status) => log::info!("[{finished}/{total}] {id}: {status}"),
+                        Err(_) => log::error!("[{finished}/{total}] {id}: failed"),
+                    }
+
+                    results.push((id.clone(), result));
+                });
+            }
+        });
+
+        results.into_inner().unwrap()
     }
 
     /// Ensure a tool that matches the given spec is installed.
-    fn install_inexact(&self, spec: &ToolSpec, trust: TrustMode) -> anyhow::Result<ToolId> {
+    pub fn install_inexact(
+        &self,
+        spec: &ToolSpec,
+        trust: &TrustMode,
+        manifest: Option<&Path>,
+    ) -> anyhow::Result<ToolId> {
         let installed_path = self.storage_dir.join("installed.txt");
         let installed = InstalledToolsCache::read(&installed_path)?;
 
-        self.trust_check(spec.name(), trust)?;
+        // With strict trust, whether a tool is trusted depends on its version,
+        // which we only know once we've picked a release. Either way, we check
+        // again once we know the version.
+        if !self.settings.strict_trust {
+            self.trust_check(spec.name(), None, trust, manifest)?;
+        }
 
         log::info!("Installing tool: {}", spec);
 
         log::debug!("Fetching GitHub releases...");
-        let github = self
-            .github
-            .get_or_init(|| GitHubSource::new(self.auth.as_ref()));
-        let mut releases = github.get_all_releases(spec.name())?;
+        let mut releases = self.github().get_all_releases(spec.name())?;
         releases.sort_by(|a, b| a.version.cmp(&b.version).reverse());
 
         log::trace!("All releases found: {:#?}", releases);
@@ -200,45 +418,23 @@ impl ToolStorage {
 
             let id = ToolId::new(spec.name().clone(), release.version.clone());
 
-            if installed.tools.contains(&id) {
+            if self.is_installed(&installed, &id) {
                 log::debug!("Tool is already installed.");
                 return Ok(id);
             }
 
-            let mut compatible_assets = self.get_compatible_assets(release);
-            if compatible_assets.is_empty() {
+            self.trust_check(id.name(), Some(id.version()), trust, manifest)?;
+
+            if release.compatible_assets(Target::host()).is_empty() {
                 log::warn!(
-                    "Version {} was compatible, but had no assets compatible with your platform.",
-                    release.version
+                    "Version {} was compatible, but had no assets compatible with your platform ({}).",
+                    release.version,
+                    Target::host()
                 );
                 continue;
             }
 
-            self.sort_assets_by_preference(&mut compatible_assets);
-            let asset = &compatible_assets[0];
-
-            log::info!(
-                "Downloading {} v{} ({})...",
-                spec.name(),
-                release.version,
-                asset.name
-            );
-            let artifact = github.download_asset(&asset.url)?;
-
-            self.install_artifact(&id, artifact).with_context(|| {
-                format!(
-                    "Could not install asset {} from tool {} release v{}",
-                    asset.name,
-                    id.name(),
-                    release.version
-                )
-            })?;
-
-            InstalledToolsCache::add(&installed_path, &id)
-                .context("Could not write installed tools cache file")?;
-
-            log::info!("{} v{} installed successfully.", id.name(), release.version);
-
+            self.download_and_install(&id, release, manifest)?;
             return Ok(id);
         }
 
@@ -246,32 +442,55 @@ impl ToolStorage {
     }
 
     /// Ensure a tool with the given tool ID is installed.
-    fn install_exact(&self, id: &ToolId, trust: TrustMode) -> anyhow::Result<()> {
+    fn install_exact(
+        &self,
+        id: &ToolId,
+        trust: &TrustMode,
+        manifest: Option<&Path>,
+    ) -> anyhow::Result<InstallStatus> {
         let installed_path = self.storage_dir.join("installed.txt");
         let installed = InstalledToolsCache::read(&installed_path)?;
-        let is_installed = installed.tools.contains(id);
 
-        if is_installed {
-            return Ok(());
+        if self.is_installed(&installed, id) {
+            return Ok(InstallStatus::AlreadyInstalled);
         }
 
-        self.trust_check(id.name(), trust)?;
+        self.trust_check(id.name(), Some(id.version()), trust, manifest)?;
 
         log::info!("Installing tool: {id}");
 
         log::debug!("Fetching GitHub release...");
-        let github = self
-            .github
-            .get_or_init(|| GitHubSource::new(self.auth.as_ref()));
-        let release = github.get_release(id)?;
+        let release = self.github().get_release(id)?;
+
+        self.download_and_install(id, &release, manifest)
+    }
+
+    /// Download and install a release of a tool, unless another process
+    /// installs it first while we wait for its lock.
+    fn download_and_install(
+        &self,
+        id: &ToolId,
+        release: &Release,
+        manifest: Option<&Path>,
+    ) -> anyhow::Result<InstallStatus> {
+        let installed_path = self.storage_dir.join("installed.txt");
+        let _lock = self.lock_tool(id)?;
 
-        let mut compatible_assets = self.get_compatible_assets(&release);
-        if compatible_assets.is_empty() {
-            bail!("Tool {id} was found, but no assets were compatible with your system.");
+        // Another process might have installed this tool while we were
+        // waiting for the lock.
+        let installed = InstalledToolsCache::read(&installed_path)?;
+        if self.is_installed(&installed, id) {
+            log::debug!("Tool {id} was installed by another process.");
+            return Ok(InstallStatus::AlreadyInstalled);
         }
 
-        self.sort_assets_by_preference(&mut compatible_assets);
-        let asset = &compatible_assets[0];
+        let host = Target::host();
+        let asset = match release.compatible_assets(host).first() {
+            Some(asset) => *asset,
+            None => bail!(
+                "Tool {id} was found, but no assets were compatible with your system ({host})."
+            ),
+        };
 
         log::info!(
             "Downloading {} v{} ({})...",
@@ -279,9 +498,18 @@ impl ToolStorage {
             release.version,
             asset.name
         );
-        let artifact = github.download_asset(&asset.url)?;
+        let label = format!("{} v{}", id.name(), release.version);
+        let download = self
+            .github()
+            .download_asset(&asset.url, &label, &self.staging_dir())?;
+        log::debug!(
+            "Downloaded {} bytes with SHA-256 {}",
+            download.size,
+            download.sha256
+        );
+        self.record_download(id, &asset.url, &download, manifest);
 
-        self.install_artifact(id, artifact).with_context(|| {
+        self.install_artifact(id, download.file).with_context(|| {
             format!(
                 "Could not install asset {} from tool {} release v{}",
                 asset.name,
@@ -292,99 +520,341 @@ impl ToolStorage {
 
         InstalledToolsCache::add(&installed_path, id)
             .context("Could not write installed tools cache file")?;
+        AuditLog::record(
+            &self.home,
+            AuditEvent::Install { tool: id.clone() },
+            manifest,
+        );
 
         log::info!("{} v{} installed successfully.", id.name(), release.version);
 
-        Ok(())
+        Ok(InstallStatus::Installed)
     }
 
-    /// Picks the best asset out of the list of assets.
-    fn sort_assets_by_preference(&self, assets: &mut [Asset]) {
-        assets.sort_by(|a, b| a.arch.cmp(&b.arch).then(a.toolchain.cmp(&b.toolchain)));
+    fn record_download(
+        &self,
+        id: &ToolId,
+        url: &str,
+        download: &Download,
+        manifest: Option<&Path>,
+    ) {
+        let event = AuditEvent::Download {
+            tool: id.clone(),
+            url: url.to_owned(),
+            size: download.size,
+            sha256: download.sha256.clone(),
+        };
+
+        AuditLog::record(&self.home, event, manifest);
     }
 
-    /// Returns a list of compatible assets from the given release.
-    fn get_compatible_assets(&self, release: &Release) -> Vec<Asset> {
-        // If any assets list an OS or architecture that's compatible with
-        // ours, we want to make that part of our filter criteria.
-        let any_has_os = release
-            .assets
-            .iter()
-            .any(|asset| asset.os.map(|os| os.compatible()).unwrap_or(false));
-        let any_has_arch = release
-            .assets
-            .iter()
-            .any(|asset| asset.arch.is_some() && asset.compatible());
+    fn github(&self) -> &GitHubSource {
+        self.github
+            .get_or_init(|| GitHubSource::new(self.credentials()))
+    }
 
-        release
-            .assets
-            .iter()
-            .filter(|asset| {
-                // If any release has an OS that matched, filter out any
-                // releases that don't match.
-                let compatible_os = asset.os.map(|os| os.compatible()).unwrap_or(false);
-                if any_has_os && !compatible_os {
-                    return false;
-                }
+    fn credentials(&self) -> CredentialStore {
+        let helper = self.settings.credential_helper.clone();
+        CredentialStore::new(self.auth.clone(), helper)
+    }
 
-                // If any release has an OS and an architecture that matched
-                // our platform, filter out any releases that don't match.
-                let compatible = asset.compatible();
-                if any_has_os && any_has_arch && !compatible {
-                    return false;
-                }
+    /// Make sure the given tool is trusted, asking the user if needed. If a
+    /// version is given and strict trust is enabled, only trust for that
+    /// version counts.
+    fn trust_check(
+        &self,
+        name: &ToolName,
+        version: Option<&Version>,
+        mode: &TrustMode,
+        manifest: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        if let TrustMode::Policy(policy) = mode {
+            if policy.is_trusted(name, version) {
+                return Ok(());
+            }
 
-                true
-            })
-            .cloned()
-            .collect()
-    }
+            return Err(TrustError::NotInPolicy {
+                name: name.clone(),
+                version: version.cloned(),
+                policy: policy.path.clone(),
+            }
+            .into());
+        }
 
-    fn trust_check(&self, name: &ToolName, mode: TrustMode) -> anyhow::Result<()> {
-        let status = self.trust_status(name)?;
+        let version = version.filter(|_| self.settings.strict_trust);
+        let status = self.trust_status(name, version)?;
 
         if status == TrustStatus::NotTrusted {
-            if mode == TrustMode::Check {
+            if let TrustMode::Check = mode {
+                // If other versions of this tool are trusted, the user should
+                // know that this is a new version rather than a new tool.
+                let trusted_ranges: Vec<VersionReq> = match version {
+                    Some(_) => {
+                        let unversioned_ranges = self.unversioned_ranges(name)?;
+                        self.trust_entries()?
+                            .into_iter()
+                            .filter(|entry| entry.pattern.matches(name))
+                            .flat_map(|entry| match entry.versions {
+                                Some(versions) => vec![versions],
+                                None => unversioned_ranges.clone(),
+                            })
+                            .unique()
+                            .collect()
+                    }
+                    None => Vec::new(),
+                };
+
                 // If the terminal isn't interactive, tell the user that they
                 // need to open an interactive terminal to trust this tool.
                 if atty::isnt(atty::Stream::Stderr) {
-                    bail!(
-                        "Tool {name} has never been installed. \
-                         Run `aftman add {name}` in your terminal to install it and trust this tool.",
-                    );
+                    return Err(match version {
+                        Some(version) if !trusted_ranges.is_empty() => {
+                            TrustError::OutsideTrustedVersions {
+                                name: name.clone(),
+                                version: version.clone(),
+                                trusted: trusted_ranges,
+                            }
+                        }
+                        _ => TrustError::NotInteractive(name.clone()),
+                    }
+                    .into());
                 }
 
                 // Since the terminal is interactive, ask the user if they're
                 // sure they want to install this tool.
+                let prompt = match version {
+                    Some(version) if !trusted_ranges.is_empty() => format!(
+                        "Tool {} is only trusted for versions {}. Install version {}?",
+                        name,
+                        trusted_ranges.iter().join(", "),
+                        version
+                    ),
+                    _ => format!("Tool {} has never been installed before. Install it?", name),
+                };
                 let proceed = dialoguer::Confirm::new()
-                    .with_prompt(format!(
-                        "Tool {} has never been installed before. Install it?",
-                        name
-                    ))
+                    .with_prompt(prompt)
                     .interact_opt()?;
 
                 if let Some(false) | None = proceed {
-                    bail!(
-                        "Tool {name} is not trusted. \
-                         Run `aftman trust {name}` in your terminal to trust it.",
-                    );
+                    return Err(TrustError::Declined(name.clone()).into());
                 }
             }
 
-            TrustCache::add(&self.home, name.clone())?;
+            let rule = TrustRule::new(name.clone().into(), version.map(default_range));
+            if TrustCache::add(&self.home, rule.clone())? {
+                AuditLog::record(&self.home, AuditEvent::Trust { rule }, manifest);
+            }
         }
 
         Ok(())
     }
 
-    fn trust_status(&self, name: &ToolName) -> anyhow::Result<TrustStatus> {
+    /// Tells whether the given tool is trusted. If a version is given, entries
+    /// that only trust other versions of the tool are ignored.
+    pub fn trust_status(
+        &self,
+        name: &ToolName,
+        version: Option<&Version>,
+    ) -> anyhow::Result<TrustStatus> {
         let trusted = TrustCache::read(&self.home)?;
-        let is_trusted = trusted.tools.contains(name);
-        if is_trusted {
-            Ok(TrustStatus::Trusted)
+        let unversioned_ranges = match version {
+            Some(_) => self.unversioned_ranges(name)?,
+            None => Vec::new(),
+        };
+        if trusted.is_trusted(name, version, &unversioned_ranges) {
+            return Ok(TrustStatus::Trusted);
+        }
+
+        for path in &self.settings.trust_policies {
+            let policy = TrustPolicy::load(&self.home.path().join(path))?;
+            if policy.is_trusted(name, version) {
+                return Ok(TrustStatus::Trusted);
+            }
+        }
+
+        Ok(TrustStatus::NotTrusted)
+    }
+
+    /// With strict trust, entries in trusted.txt without a version range only
+    /// cover versions compatible with one that's already installed. These
+    /// entries were added before strict trust was turned on, so a new major
+    /// version still needs to be trusted. If no version of the tool is
+    /// installed, they cover whichever version is installed first.
+    fn unversioned_ranges(&self, name: &ToolName) -> anyhow::Result<Vec<VersionReq>> {
+        Ok(self
+            .installed_tools()?
+            .iter()
+            .filter(|id| id.name() == name)
+            .map(|id| default_range(id.version()))
+            .unique()
+            .collect())
+    }
+
+    /// The version of the given tool that trust checks should consider, which
+    /// is only relevant with strict trust.
+    pub fn trust_version<'a>(&self, id: &'a ToolId) -> Option<&'a Version> {
+        if self.settings.strict_trust {
+            Some(id.version())
         } else {
-            Ok(TrustStatus::NotTrusted)
+            None
+        }
+    }
+
+    /// Every trusted tool or pattern, including those from trust policies.
+    pub fn trust_entries(&self) -> anyhow::Result<Vec<TrustEntry>> {
+        let mut entries: Vec<TrustEntry> = TrustCache::read(&self.home)?
+            .tools
+            .into_iter()
+            .map(|rule| TrustEntry {
+                pattern: rule.pattern,
+                versions: rule.versions,
+                policy: None,
+            })
+            .collect();
+
+        for path in &self.settings.trust_policies {
+            let path = self.home.path().join(path);
+            let policy = TrustPolicy::load(&path)?;
+
+            entries.extend(policy.trusted.into_iter().map(|rule| TrustEntry {
+                pattern: rule.pattern,
+                versions: rule.versions,
+                policy: Some(path.clone()),
+            }));
+        }
+
+        Ok(entries)
+    }
+
+    /// Re-hash the executable of an installed tool and compare it against
+    /// the hash recorded when it was installed.
+    pub fn verify(&self, id: &ToolId) -> anyhow::Result<VerifyStatus> {
+        let exe_path = self.exe_path(id);
+        if !exe_path.is_file() {
+            return Ok(VerifyStatus::Missing);
+        }
+
+        let hashes = ToolHashes::read(&self.hashes_path())?;
+        let expected = match hashes.get(id) {
+            Some(expected) => expected,
+            None => return Ok(VerifyStatus::Unrecorded),
+        };
+
+        let actual = sha256_file(&exe_path)
+            .with_context(|| format!("Could not read {}", exe_path.display()))?;
+
+        if actual == expected {
+            Ok(VerifyStatus::Ok)
+        } else {
+            Ok(VerifyStatus::Mismatch)
+        }
+    }
+
+    /// Record the hash of an installed tool's executable as it is now. This
+    /// trusts whatever is on disk, so it's only done when the user asks.
+    pub fn record_hash(&self, id: &ToolId) -> anyhow::Result<()> {
+        let exe_path = self.exe_path(id);
+        let sha256 = sha256_file(&exe_path)
+            .with_context(|| format!("Could not read {}", exe_path.display()))?;
+
+        ToolHashes::set(&self.hashes_path(), id, &sha256)
+            .context("Could not record hash of executable")
+    }
+
+    /// Download and install a tool again, replacing whatever is currently in
+    /// tool-storage.
+    pub fn repair(&self, id: &ToolId) -> anyhow::Result<()> {
+        let installed_path = self.storage_dir.join("installed.txt");
+        InstalledToolsCache::remove(&installed_path, std::slice::from_ref(id))?;
+
+        // If the tool can't be installed again, it's still listed so that a
+        // later repair can try again.
+        if let Err(err) = self.install_exact(id, &TrustMode::Check, None) {
+            InstalledToolsCache::add(&installed_path, id)?;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Every installed tool.
+    pub fn installed_tools(&self) -> anyhow::Result<BTreeSet<ToolId>> {
+        let installed_path = self.storage_dir.join("installed.txt");
+        Ok(InstalledToolsCache::read(&installed_path)?.tools)
+    }
+
+    /// Delete every installed version of every tool matching `rule` from
+    /// tool-storage, returning the tools that were removed.
+    pub fn uninstall(&self, rule: &TrustRule) -> anyhow::Result<Vec<ToolId>> {
+        let installed_path = self.storage_dir.join("installed.txt");
+        let removed: Vec<ToolId> = InstalledToolsCache::read(&installed_path)?
+            .tools
+            .into_iter()
+            .filter(|id| rule.allows(id.name(), Some(id.version())))
+            .collect();
+
+        // The list of installed tools is updated first so that an interrupted
+        // uninstall never leaves tools that are listed but missing.
+        InstalledToolsCache::remove(&installed_path, &removed)?;
+        ToolHashes::remove(&self.hashes_path(), &removed)?;
+
+        for id in &removed {
+            let version_dir = self.exe_path(id);
+            let version_dir = version_dir.parent().unwrap();
+
+            log::info!("Removing {id}");
+
+            // The lock file is kept, since deleting it would let a process
+            // that's waiting on it and a process that creates a new one both
+            // think they hold the lock.
+            let _lock = self.lock_tool(id)?;
+            match fs_err::remove_dir_all(version_dir) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            AuditLog::record(&self.home, AuditEvent::Remove { tool: id.clone() }, None);
+        }
+
+        Ok(removed)
+    }
+
+    /// Remove the shim for the given alias from the bin directory, if there is
+    /// one.
+    pub fn unlink(&self, alias: &ToolAlias) -> anyhow::Result<bool> {
+        let link_path = self
+            .bin_dir
+            .join(format!("{}{}", alias.as_ref(), EXE_SUFFIX));
+
+        match fs_err::remove_file(link_path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Tells whether the given tool is currently installed.
+    pub fn is_tool_installed(&self, id: &ToolId) -> anyhow::Result<bool> {
+        let installed_path = self.storage_dir.join("installed.txt");
+        let installed = InstalledToolsCache::read(&installed_path)?;
+
+        Ok(self.is_installed(&installed, id))
+    }
+
+    /// Tells whether the given tool is listed as installed and its executable
+    /// is actually present on disk.
+    fn is_installed(&self, installed: &InstalledToolsCache, id: &ToolId) -> bool {
+        if !installed.tools.contains(id) {
+            return false;
+        }
+
+        if !self.exe_path(id).is_file() {
+            log::warn!("Tool {id} is listed as installed, but its executable is missing.");
+            return false;
         }
+
+        true
     }
 
     fn install_executable(&self, id: &ToolId, mut contents: impl Read) -> anyhow::Result<()> {
@@ -392,22 +862,92 @@ impl ToolStorage {
 
         fs_err::create_dir_all(output_path.parent().unwrap())?;
 
-        let mut output = BufWriter::new(File::create(&output_path)?);
+        // The executable is written into the staging directory first and only
+        // moved into place once it's complete. The staging directory lives
+        // inside tool-storage so that the final rename never crosses
+        // filesystems.
+        let mut staged = tempfile::Builder::new()
+            .prefix("install-")
+            .tempfile_in(self.staging_dir())
+            .context("Could not create staging file")?;
+
+        let mut output = HashingWriter::new(BufWriter::new(staged.as_file_mut()));
         io::copy(&mut contents, &mut output)?;
         output.flush()?;
+        let sha256 = output.finish();
 
         #[cfg(unix)]
         {
             use std::fs::{set_permissions, Permissions};
             use std::os::unix::fs::PermissionsExt;
 
-            set_permissions(&output_path, Permissions::from_mode(0o755))
+            set_permissions(staged.path(), Permissions::from_mode(0o755))
                 .context("failed to mark executable as executable")?;
         }
 
+        staged.as_file().sync_all()?;
+        staged
+            .persist(&output_path)
+            .with_context(|| format!("Could not move executable to {}", output_path.display()))?;
+
+        ToolHashes::set(&self.hashes_path(), id, &sha256)
+            .context("Could not record hash of executable")?;
+
+        Ok(())
+    }
+
+    /// Removes any leftover files from installs that were interrupted before
+    /// they could finish, like by Ctrl-C or running out of disk space.
+    fn clean_staging_dir(&self) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+
+        for entry in fs_err::read_dir(self.staging_dir())? {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+
+            // Files that are still fresh might belong to an install that's
+            // running in another process right now.
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age < STALE_STAGING_AGE {
+                continue;
+            }
+
+            let path = entry.path();
+            log::debug!("Cleaning up interrupted install {}", path.display());
+
+            let result = if entry.file_type()?.is_dir() {
+                fs_err::remove_dir_all(&path)
+            } else {
+                fs_err::remove_file(&path)
+            };
+
+            if let Err(err) = result {
+                log::warn!("Could not clean up interrupted install: {err}");
+            }
+        }
+
         Ok(())
     }
 
+    /// Locks the given tool version so that only one process at a time can
+    /// install it.
+    fn lock_tool(&self, id: &ToolId) -> anyhow::Result<FileLock> {
+        let mut path = self.storage_dir.clone();
+        path.push(id.name().scope());
+        path.push(id.name().name());
+        path.push(format!("{}.lock", id.version()));
+
+        FileLock::acquire(&path)
+    }
+
+    fn hashes_path(&self) -> PathBuf {
+        self.storage_dir.join("hashes.txt")
+    }
+
+    fn staging_dir(&self) -> PathBuf {
+        self.storage_dir.join(".staging")
+    }
+
     fn install_artifact(&self, id: &ToolId, artifact: impl Read + Seek) -> anyhow::Result<()> {
         let output_path = self.exe_path(id);
         let expected_name = format!("{}{EXE_SUFFIX}", id.name().name());
@@ -442,17 +982,42 @@ impl ToolStorage {
     }
 
     fn link(&self, alias: &ToolAlias) -> anyhow::Result<()> {
-        let self_path =
-            current_exe().context("Failed to discover path to the Aftman executable")?;
-
         let link_name = format!("{}{}", alias.as_ref(), EXE_SUFFIX);
         let link_path = self.bin_dir.join(link_name);
 
+        // Symlinks only work if Aftman has been installed into its bin
+        // directory. If it hasn't, we fall back to copying.
+        #[cfg(unix)]
+        if self.bin_dir.join("aftman").is_file() {
+            return self
+                .symlink_to_aftman(&link_path)
+                .context("Failed to create Aftman alias");
+        }
+
+        let self_path =
+            current_exe().context("Failed to discover path to the Aftman executable")?;
+
         fs_err::copy(self_path, link_path).context("Failed to create Aftman alias")?;
         Ok(())
     }
 
-    fn exe_path(&self, id: &ToolId) -> PathBuf {
+    /// Replace the file at `link_path` with a symlink to the Aftman executable
+    /// in the bin directory.
+    #[cfg(unix)]
+    fn symlink_to_aftman(&self, link_path: &Path) -> anyhow::Result<()> {
+        let name = link_path.file_name().unwrap().to_string_lossy();
+        let temp_path = self.bin_dir.join(format!(".{name}.{}", std::process::id()));
+
+        // The link is created under a temporary name and renamed over the
+        // old file so that the alias never disappears, even briefly.
+        std::os::unix::fs::symlink("aftman", &temp_path)
+            .with_context(|| format!("Could not create link {}", temp_path.display()))?;
+        fs_err::rename(&temp_path, link_path)?;
+
+        Ok(())
+    }
+
+    pub fn exe_path(&self, id: &ToolId) -> PathBuf {
         let mut dir = self.storage_dir.clone();
         dir.push(id.name().scope());
         dir.push(id.name().name());
@@ -462,6 +1027,92 @@ impl ToolStorage {
     }
 }
 
+/// The outcome of trying to install a single tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStatus {
+    Installed,
+    AlreadyInstalled,
+    Untrusted,
+    Failed,
+}
+
+impl fmt::Display for InstallStatus {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Self::Installed => "installed",
+            Self::AlreadyInstalled => "already installed",
+            Self::Untrusted => "skipped (untrusted)",
+            Self::Failed => "failed",
+        };
+
+        formatter.write_str(status)
+    }
+}
+
+/// Describes what happened to every tool during a call to
+/// `ToolStorage::install_all`.
+#[derive(Debug)]
+pub struct InstallSummary {
+    pub tools: BTreeMap<ToolId, InstallStatus>,
+    pub failures: Vec<(ToolId, anyhow::Error)>,
+    pub trust_errors: Vec<(ToolId, anyhow::Error)>,
+    skip_untrusted: bool,
+}
+
+impl InstallSummary {
+    /// Print a table of every tool and its status to stderr.
+    pub fn print(&self) {
+        if self.tools.is_empty() {
+            return;
+        }
+
+        let width = self
+            .tools
+            .keys()
+            .map(|id| id.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        eprintln!();
+        eprintln!("{:width$}  Status", "Tool");
+        for (id, status) in &self.tools {
+            eprintln!("{:width$}  {status}", id.to_string());
+        }
+    }
+
+    /// Turns any failures from the install into an error.
+    pub fn into_result(self) -> anyhow::Result<()> {
+        let mut report = Vec::new();
+
+        if !self.failures.is_empty() {
+            report.push(format!(
+                "Installation failed for the following tools:\n{}",
+                self.failures
+                    .iter()
+                    .map(|(id, e)| format!("    {id}: {e:#}"))
+                    .join("\n")
+            ));
+        }
+
+        if !self.trust_errors.is_empty() && !self.skip_untrusted {
+            report.push(format!(
+                "Installation trust check failed for the following tools:\n{}",
+                self.trust_errors
+                    .iter()
+                    .map(|(_, e)| format!("    {e}"))
+                    .join("\n")
+            ));
+        }
+
+        if !report.is_empty() {
+            bail!("{}", report.join("\n"));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct InstalledToolsCache {
     pub tools: BTreeSet<ToolId>,
@@ -489,15 +1140,260 @@ impl InstalledToolsCache {
     }
 
     pub fn add(path: &Path, id: &ToolId) -> anyhow::Result<()> {
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
         let mut cache = Self::read(path)?;
         cache.tools.insert(id.clone());
+        cache.write(path)
+    }
 
+    /// Remove the given tools from the list of installed tools. Their
+    /// executables are left alone.
+    pub fn remove(path: &Path, ids: &[ToolId]) -> anyhow::Result<()> {
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut cache = Self::read(path)?;
+        for id in ids {
+            cache.tools.remove(id);
+        }
+        cache.write(path)
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
         let mut output = String::new();
-        for tool in cache.tools {
+        for tool in &self.tools {
             writeln!(&mut output, "{}", tool).unwrap();
         }
 
-        fs_err::write(path, output)?;
+        write_atomic(path, &output)
+    }
+}
+
+/// The result of checking an installed tool's executable against the hash
+/// recorded when it was installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    Ok,
+    Mismatch,
+    Missing,
+    Unrecorded,
+}
+
+impl VerifyStatus {
+    /// Tells whether the executable can't be trusted. Tools with no recorded
+    /// hash were installed before Aftman recorded hashes, so they don't count.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Mismatch | Self::Missing)
+    }
+}
+
+impl fmt::Display for VerifyStatus {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Self::Ok => "ok",
+            Self::Mismatch => "executable does not match its recorded hash",
+            Self::Missing => "executable is missing",
+            Self::Unrecorded => "no hash was recorded when it was installed",
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+/// The SHA-256 hash of every installed executable, recorded at install time,
+/// stored in tool-storage/hashes.txt.
+#[derive(Debug, Default)]
+pub struct ToolHashes {
+    hashes: BTreeMap<ToolId, String>,
+}
+
+impl ToolHashes {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = match fs_err::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) => {
+                if err.kind() == io::ErrorKind::NotFound {
+                    String::new()
+                } else {
+                    bail!(err);
+                }
+            }
+        };
+
+        let hashes = contents
+            .lines()
+            .filter_map(|line| {
+                let (id, hash) = line.split_once(' ')?;
+                Some((id.parse().ok()?, hash.trim().to_owned()))
+            })
+            .collect();
+
+        Ok(Self { hashes })
+    }
+
+    pub fn get(&self, id: &ToolId) -> Option<&str> {
+        self.hashes.get(id).map(String::as_str)
+    }
+
+    pub fn set(path: &Path, id: &ToolId, hash: &str) -> anyhow::Result<()> {
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut cache = Self::read(path)?;
+        cache.hashes.insert(id.clone(), hash.to_owned());
+        cache.write(path)
+    }
+
+    pub fn remove(path: &Path, ids: &[ToolId]) -> anyhow::Result<()> {
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut cache = Self::read(path)?;
+        for id in ids {
+            cache.hashes.remove(id);
+        }
+        cache.write(path)
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let mut output = String::new();
+        for (id, hash) in &self.hashes {
+            writeln!(&mut output, "{} {}", id, hash).unwrap();
+        }
+
+        write_atomic(path, &output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_executable_is_not_installed() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let storage = ToolStorage::new(&home)?;
+
+        let id: ToolId = "foo/bar@1.0.0".parse()?;
+        let installed_path = storage.storage_dir.join("installed.txt");
+        InstalledToolsCache::add(&installed_path, &id)?;
+
+        let installed = InstalledToolsCache::read(&installed_path)?;
+        assert!(!storage.is_installed(&installed, &id));
+
+        storage.install_executable(&id, io::Cursor::new(b"hello"))?;
+        assert!(storage.is_installed(&installed, &id));
+        assert_eq!(fs_err::read(storage.exe_path(&id))?, b"hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn trust_policy_mode() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let storage = ToolStorage::new(&home)?;
+        TrustCache::add(&home, "foo/baz".parse()?)?;
+
+        let path = home.path().join("policy.toml");
+        fs_err::write(&path, "trusted = [\"foo/bar@^1\"]\n")?;
+        let mode = TrustMode::Policy(TrustPolicy::load(&path)?);
+
+        let name: ToolName = "foo/bar".parse()?;
+        storage.trust_check(&name, Some(&Version::new(1, 2, 0)), &mode, None)?;
+        assert!(storage
+            .trust_check(&name, Some(&Version::new(2, 0, 0)), &mode, None)
+            .is_err());
+
+        // Tools trusted outside of the policy are not trusted in this mode,
+        // and nothing the policy allows is recorded.
+        assert!(storage
+            .trust_check(&"foo/baz".parse()?, None, &mode, None)
+            .is_err());
+        assert_eq!(TrustCache::read(&home)?.tools.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_trust_unversioned_entries() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let mut storage = ToolStorage::new(&home)?;
+        storage.settings.strict_trust = true;
+        TrustCache::add(&home, "foo/bar".parse()?)?;
+
+        // Before any version is installed, the entry covers the first one.
+        let name: ToolName = "foo/bar".parse()?;
+        let status = storage.trust_status(&name, Some(&Version::new(1, 2, 0)))?;
+        assert_eq!(status, TrustStatus::Trusted);
+
+        let id: ToolId = "foo/bar@1.2.0".parse()?;
+        let installed_path = storage.storage_dir.join("installed.txt");
+        storage.install_executable(&id, io::Cursor::new(b"hello"))?;
+        InstalledToolsCache::add(&installed_path, &id)?;
+
+        let status = storage.trust_status(&name, Some(&Version::new(1, 5, 0)))?;
+        assert_eq!(status, TrustStatus::Trusted);
+        let status = storage.trust_status(&name, Some(&Version::new(2, 0, 0)))?;
+        assert_eq!(status, TrustStatus::NotTrusted);
+
+        // Without strict trust, the entry still covers every version.
+        storage.settings.strict_trust = false;
+        storage.trust_check(&name, Some(&Version::new(2, 0, 0)), &TrustMode::Check, None)?;
+        assert_eq!(TrustCache::read(&home)?.tools.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn uninstall_matching_tools() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let storage = ToolStorage::new(&home)?;
+        let installed_path = storage.storage_dir.join("installed.txt");
+
+        let ids: Vec<ToolId> = ["foo/bar@1.0.0", "foo/bar@2.0.0", "foo/baz@1.0.0"]
+            .iter()
+            .map(|id| id.parse())
+            .collect::<anyhow::Result<_>>()?;
+
+        for id in &ids {
+            storage.install_executable(id, io::Cursor::new(b"hello"))?;
+            InstalledToolsCache::add(&installed_path, id)?;
+        }
+
+        let removed = storage.uninstall(&"foo/bar".parse()?)?;
+        assert_eq!(removed, &ids[..2]);
+        assert!(!storage.exe_path(&ids[0]).exists());
+        assert!(!storage.exe_path(&ids[1]).exists());
+        assert!(storage.is_tool_installed(&ids[2])?);
+
+        let removed = storage.uninstall(&"foo/*".parse()?)?;
+        assert_eq!(removed, &ids[2..]);
+        assert!(InstalledToolsCache::read(&installed_path)?.tools.is_empty());
+        assert!(ToolHashes::read(&storage.hashes_path())?.hashes.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_executables() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let storage = ToolStorage::new(&home)?;
+
+        let id: ToolId = "foo/bar@1.0.0".parse()?;
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Missing);
+
+        storage.install_executable(&id, io::Cursor::new(b"hello"))?;
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Ok);
+
+        fs_err::write(storage.exe_path(&id), b"hellp")?;
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Mismatch);
+
+        fs_err::remove_file(storage.hashes_path())?;
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Unrecorded);
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Unrecorded);
+
+        storage.record_hash(&id)?;
+        assert_eq!(storage.verify(&id)?, VerifyStatus::Ok);
+
         Ok(())
     }
 }
@@ -1,16 +1,28 @@
 use std::collections::BTreeSet;
-use std::fmt::Write;
+use std::fmt::{self, Write};
 use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use anyhow::bail;
+use anyhow::{bail, format_err, Context};
+use itertools::Itertools;
+use semver::{Version, VersionReq};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::config::write_atomic;
 use crate::home::Home;
 use crate::tool_name::ToolName;
+use crate::tool_pattern::ToolPattern;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub enum TrustMode {
     Check,
     NoCheck,
+
+    /// Only tools allowed by the given policy are trusted, without prompting.
+    /// Every other source of trust is ignored.
+    Policy(TrustPolicy),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,9 +31,176 @@ pub enum TrustStatus {
     NotTrusted,
 }
 
+/// Returned when a tool can't be installed because it isn't trusted.
+#[derive(Debug)]
+pub enum TrustError {
+    /// The tool isn't trusted and we have no terminal to ask the user with.
+    NotInteractive(ToolName),
+
+    /// The user was asked and declined to trust the tool.
+    Declined(ToolName),
+
+    /// Only other versions of the tool are trusted, and we have no terminal to
+    /// ask the user with.
+    OutsideTrustedVersions {
+        name: ToolName,
+        version: Version,
+        trusted: Vec<VersionReq>,
+    },
+
+    /// The tool isn't allowed by the trust policy that was given.
+    NotInPolicy {
+        name: ToolName,
+        version: Option<Version>,
+        policy: PathBuf,
+    },
+}
+
+impl fmt::Display for TrustError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotInteractive(name) => write!(
+                formatter,
+                "Tool {name} has never been installed. \
+                 Run `aftman add {name}` in your terminal to install it and trust this tool."
+            ),
+            Self::Declined(name) => write!(
+                formatter,
+                "Tool {name} is not trusted. \
+                 Run `aftman trust {name}` in your terminal to trust it."
+            ),
+            Self::OutsideTrustedVersions {
+                name,
+                version,
+                trusted,
+            } => {
+                let trusted = trusted.iter().map(ToString::to_string).join(", ");
+                let range = default_range(version);
+
+                write!(
+                    formatter,
+                    "Tool {name} is only trusted for versions {trusted}, but version {version} was requested. \
+                     Run `aftman trust {name}@{range}` in your terminal to trust it."
+                )
+            }
+            Self::NotInPolicy {
+                name,
+                version: Some(version),
+                policy,
+            } => write!(
+                formatter,
+                "Tool {name} v{version} is not allowed by the trust policy at {}.",
+                policy.display()
+            ),
+            Self::NotInPolicy {
+                name,
+                version: None,
+                policy,
+            } => write!(
+                formatter,
+                "Tool {name} is not allowed by the trust policy at {}.",
+                policy.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TrustError {}
+
+/// One entry in the set of trusted tools: a tool name or pattern, optionally
+/// limited to a range of versions, like `rojo-rbx/rojo@^7`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustRule {
+    pub pattern: ToolPattern,
+    pub versions: Option<VersionReq>,
+}
+
+impl TrustRule {
+    pub fn new(pattern: ToolPattern, versions: Option<VersionReq>) -> Self {
+        Self { pattern, versions }
+    }
+
+    /// Tells whether this rule trusts the given tool. If no version is given,
+    /// version ranges are ignored.
+    pub fn allows(&self, name: &ToolName, version: Option<&Version>) -> bool {
+        if !self.pattern.matches(name) {
+            return false;
+        }
+
+        match (&self.versions, version) {
+            (Some(versions), Some(version)) => versions.matches(version),
+            _ => true,
+        }
+    }
+}
+
+/// The version range that Aftman records when strict trust is enabled and the
+/// user trusts a specific version: every version that is semver compatible
+/// with it.
+pub fn default_range(version: &Version) -> VersionReq {
+    let range = match (version.major, version.minor) {
+        (0, 0) => format!("^0.0.{}", version.patch),
+        (0, minor) => format!("^0.{minor}"),
+        (major, _) => format!("^{major}"),
+    };
+
+    range.parse().unwrap()
+}
+
+impl From<ToolPattern> for TrustRule {
+    fn from(pattern: ToolPattern) -> Self {
+        Self::new(pattern, None)
+    }
+}
+
+impl fmt::Display for TrustRule {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &self.versions {
+            Some(versions) => write!(formatter, "{}@{}", self.pattern, versions),
+            None => write!(formatter, "{}", self.pattern),
+        }
+    }
+}
+
+impl FromStr for TrustRule {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let mut pattern_versions = value.splitn(2, '@');
+        let pattern = pattern_versions.next().unwrap().parse()?;
+
+        let versions = pattern_versions
+            .next()
+            .map(|versions| {
+                versions.parse().with_context(|| {
+                    format_err!("Invalid version range in trusted tool \"{}\"", value)
+                })
+            })
+            .transpose()?;
+
+        Ok(Self::new(pattern, versions))
+    }
+}
+
+impl Serialize for TrustRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TrustRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+/// The tools that the user has chosen to trust, stored in ~/.aftman/trusted.txt.
+/// Each line is a tool name or a pattern like `our-org/*`, optionally followed
+/// by a version range like `@^7`.
 #[derive(Debug)]
 pub struct TrustCache {
-    pub tools: BTreeSet<ToolName>,
+    pub tools: Vec<TrustRule>,
 }
 
 impl TrustCache {
@@ -41,28 +220,117 @@ impl TrustCache {
 
         let tools = contents
             .lines()
-            .filter_map(|line| line.parse::<ToolName>().ok())
+            .filter_map(|line| line.parse::<TrustRule>().ok())
             .collect();
 
         Ok(Self { tools })
     }
 
-    pub fn add(home: &Home, name: ToolName) -> anyhow::Result<bool> {
+    /// Tells whether any entry trusts the given tool. When a version is given
+    /// and `unversioned_ranges` isn't empty, entries without a range only
+    /// trust versions within those ranges.
+    pub fn is_trusted(
+        &self,
+        name: &ToolName,
+        version: Option<&Version>,
+        unversioned_ranges: &[VersionReq],
+    ) -> bool {
+        self.tools
+            .iter()
+            .any(|rule| match (&rule.versions, version) {
+                (None, Some(version)) if !unversioned_ranges.is_empty() => {
+                    rule.pattern.matches(name)
+                        && unversioned_ranges
+                            .iter()
+                            .any(|range| range.matches(version))
+                }
+                _ => rule.allows(name, version),
+            })
+    }
+
+    pub fn add(home: &Home, rule: TrustRule) -> anyhow::Result<bool> {
         let mut cache = Self::read(home)?;
 
-        if cache.tools.insert(name) {
-            let mut output = String::new();
-            for tool in cache.tools {
-                writeln!(&mut output, "{}", tool).unwrap();
-            }
+        if cache.tools.contains(&rule) {
+            return Ok(false);
+        }
+
+        cache.tools.push(rule);
+        cache.write(home)?;
+        Ok(true)
+    }
+
+    /// Remove an entry that was previously added. Only exact entries are
+    /// removed, so removing `foo/bar` leaves a `foo/*` entry alone.
+    pub fn remove(home: &Home, rule: &TrustRule) -> anyhow::Result<bool> {
+        let mut cache = Self::read(home)?;
+
+        let len = cache.tools.len();
+        cache.tools.retain(|existing| existing != rule);
 
-            let path = home.path().join("trusted.txt");
-            fs_err::write(path, output)?;
+        if cache.tools.len() == len {
+            return Ok(false);
+        }
+
+        cache.write(home)?;
+        Ok(true)
+    }
+
+    fn write(&self, home: &Home) -> anyhow::Result<()> {
+        let lines: BTreeSet<String> = self.tools.iter().map(ToString::to_string).collect();
 
-            return Ok(true);
+        let mut output = String::new();
+        for line in lines {
+            writeln!(&mut output, "{}", line).unwrap();
         }
 
-        Ok(false)
+        let path = home.path().join("trusted.txt");
+        write_atomic(&path, &output)
+    }
+}
+
+/// A single trust entry and where it came from, as listed by
+/// `aftman trust --list`.
+#[derive(Debug, Serialize)]
+pub struct TrustEntry {
+    pub pattern: ToolPattern,
+
+    /// The versions that are trusted, or `None` if every version is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<VersionReq>,
+
+    /// The trust policy file that this entry came from, or `None` if it's from
+    /// the user's own list of trusted tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PathBuf>,
+}
+
+/// A list of trusted tools that can be shared between users, like a file
+/// checked into a repository. Aftman only reads policy files that are listed
+/// in the user's settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrustPolicy {
+    #[serde(default)]
+    pub trusted: Vec<TrustRule>,
+
+    /// The path that this policy was loaded from.
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl TrustPolicy {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs_err::read(path)?;
+
+        let mut policy: TrustPolicy = toml::from_slice(&contents)
+            .with_context(|| format_err!("Invalid trust policy at {}", path.display()))?;
+        policy.path = path.to_owned();
+
+        Ok(policy)
+    }
+
+    pub fn is_trusted(&self, name: &ToolName, version: Option<&Version>) -> bool {
+        self.trusted.iter().any(|rule| rule.allows(name, version))
     }
 }
 
@@ -79,13 +347,88 @@ mod test {
 
         let tool_name: ToolName = "foo/bar".parse()?;
 
-        let added = TrustCache::add(&home, tool_name.clone())?;
+        let added = TrustCache::add(&home, ToolPattern::from(tool_name.clone()).into())?;
         assert!(added);
 
         let cache = TrustCache::read(&home)?;
         assert!(cache.tools.len() == 1);
-        assert!(cache.tools.contains(&tool_name));
+        assert!(cache.is_trusted(&tool_name, None, &[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        TrustCache::add(&home, "foo/bar".parse()?)?;
+        TrustCache::add(&home, "foo/*".parse()?)?;
+
+        assert!(TrustCache::remove(&home, &"foo/bar".parse()?)?);
+        assert!(!TrustCache::remove(&home, &"foo/bar".parse()?)?);
+
+        let cache = TrustCache::read(&home)?;
+        assert_eq!(cache.tools.len(), 1);
+        assert!(cache.is_trusted(&"foo/bar".parse()?, None, &[]));
 
         Ok(())
     }
+
+    #[test]
+    fn patterns() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        TrustCache::add(&home, "our-org/*".parse()?)?;
+
+        let cache = TrustCache::read(&home)?;
+        assert!(cache.is_trusted(&"our-org/linter".parse()?, None, &[]));
+        assert!(!cache.is_trusted(&"other-org/linter".parse()?, None, &[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn policy() -> anyhow::Result<()> {
+        let home = Home::new_temp()?;
+        let path = home.path().join("policy.toml");
+        fs_err::write(&path, "trusted = [\"our-org/*\", \"rojo-rbx/rojo\"]\n")?;
+
+        let policy = TrustPolicy::load(&path)?;
+        assert!(policy.is_trusted(&"our-org/linter".parse()?, None));
+        assert!(policy.is_trusted(&"rojo-rbx/rojo".parse()?, None));
+        assert!(!policy.is_trusted(&"rojo-rbx/tarmac".parse()?, None));
+
+        fs_err::write(&path, "trusted = [\"our-org\"]\n")?;
+        assert!(TrustPolicy::load(&path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn version_ranges() -> anyhow::Result<()> {
+        let rule: TrustRule = "rojo-rbx/rojo@^7".parse()?;
+        let name: ToolName = "rojo-rbx/rojo".parse()?;
+
+        assert!(rule.allows(&name, Some(&Version::new(7, 4, 0))));
+        assert!(!rule.allows(&name, Some(&Version::new(8, 0, 0))));
+        assert!(rule.allows(&name, None));
+        assert_eq!(rule.to_string(), "rojo-rbx/rojo@^7");
+
+        let rule: TrustRule = "our-org/*@^0.3".parse()?;
+        assert!(rule.allows(&"our-org/linter".parse()?, Some(&Version::new(0, 3, 9))));
+        assert!(!rule.allows(&"our-org/linter".parse()?, Some(&Version::new(0, 4, 0))));
+
+        assert!("rojo-rbx/rojo@seven".parse::<TrustRule>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_ranges() {
+        fn range(version: &str) -> String {
+            default_range(&version.parse().unwrap()).to_string()
+        }
+
+        assert_eq!(range("7.4.0"), "^7");
+        assert_eq!(range("0.3.2"), "^0.3");
+        assert_eq!(range("0.0.4"), "^0.0.4");
+    }
 }